@@ -0,0 +1,157 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, Path, Query},
+    http::{request::Parts, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use thiserror::Error;
+
+/// Header a request ID is read from, if the client sent one, and always returned in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// A failed API request, rendered as a JSON error body with a matching status code
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// The product, or other resource named in the path, doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+
+    /// A query or path parameter couldn't be parsed or makes no sense
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Database(e) if is_unavailable(e) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable, machine-readable name for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Database(e) if is_unavailable(e) => "database_unavailable",
+            ApiError::Database(_) => "internal",
+        }
+    }
+
+    /// What the client is told; database errors are logged, not returned
+    fn message(&self) -> String {
+        match self {
+            ApiError::NotFound(message) | ApiError::BadRequest(message) => message.clone(),
+            ApiError::Database(e) if is_unavailable(e) => "The database is unavailable, try again shortly".to_string(),
+            ApiError::Database(_) => "Internal server error".to_string(),
+        }
+    }
+}
+
+/// Whether the database couldn't be reached at all, as opposed to a query failing
+fn is_unavailable(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) | sqlx::Error::Tls(_) => true,
+        // Connection exceptions (08) and the server shutting down or starting up (57P)
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| code.starts_with("08") || code.starts_with("57P")),
+        _ => false,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let request_id = current_request_id();
+
+        if status.is_server_error() {
+            tracing::error!(request_id = request_id.as_deref(), "Request failed: {}", self);
+        } else {
+            tracing::info!(request_id = request_id.as_deref(), "Request rejected: {}", self);
+        }
+
+        let body = json!({
+            "error": {
+                "code": self.code(),
+                "message": self.message(),
+                "request_id": request_id,
+            }
+        });
+        (status, Json(body)).into_response()
+    }
+}
+
+/// The ID of the request being handled, if it went through `assign_request_id`
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Gives every request an ID, the client's own `x-request-id` if it sent a
+/// usable one, and returns it in the response's `x-request-id` header
+pub async fn assign_request_id(req: Request<Body>, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// `axum::extract::Query`, rejecting unparseable parameters with an `ApiError`
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Query::<T>::from_request_parts(parts, state)
+            .await
+            .map(|Query(params)| ApiQuery(params))
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))
+    }
+}
+
+/// `axum::extract::Path`, rejecting unparseable segments with an `ApiError`
+pub struct ApiPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Path::<T>::from_request_parts(parts, state)
+            .await
+            .map(|Path(params)| ApiPath(params))
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))
+    }
+}
@@ -0,0 +1,1211 @@
+pub mod error;
+
+use axum::{
+    middleware,
+    routing::get,
+    Router,
+    Json,
+    extract::State,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{EventType, Material, Money, SyncRunKind, SyncRunStatus};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::postgres::{PgArguments, Postgres};
+use sqlx::query::QueryAs;
+use sqlx::{PgPool, FromRow};
+use std::collections::HashMap;
+
+use crate::error::{ApiError, ApiPath, ApiQuery};
+
+/// Transactions are served this many at a time unless the request asks otherwise
+const DEFAULT_TRANSACTIONS_LIMIT: i64 = 100;
+const MAX_TRANSACTIONS_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+struct TransactionsParams {
+    /// Page size, up to `MAX_TRANSACTIONS_LIMIT`
+    limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    material: Option<Material>,
+    product_id: Option<String>,
+    variant_id: Option<String>,
+    event_type: Option<EventType>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    min_price: Option<Money>,
+    max_price: Option<Money>,
+    min_premium: Option<f64>,
+    max_premium: Option<f64>,
+    min_quantity: Option<i32>,
+    max_quantity: Option<i32>,
+    /// Also count every transaction matching the filters, which costs a second query
+    #[serde(default)]
+    include_total: bool,
+}
+
+/// Position after the last transaction of a page, newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TransactionCursor {
+    event_time: DateTime<Utc>,
+    id: i64,
+}
+
+impl TransactionCursor {
+    /// Encodes as `<event_time in microseconds>_<id>`, which is exact for timestamptz
+    fn encode(&self) -> String {
+        format!("{}_{}", self.event_time.timestamp_micros(), self.id)
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        let (micros, id) = cursor.split_once('_')?;
+        Some(Self {
+            event_time: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct TransactionWithProduct {
+    id: i64,
+    pure_product_id: String,
+    pure_variant_id: String,
+    name: String,
+    sku: String,
+    material: Material,
+    variant_label: String,
+    image_url: Option<String>,
+    event_time: DateTime<Utc>,
+    quantity: i32,
+    price: Money,
+    spot_premium_percentage: f64,
+    spot_premium_dollar: Money,
+    event_type: Option<EventType>,
+    event_type_confidence: Option<f64>,
+    event_type_source: Option<String>,
+    upstream_event: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TransactionsResponse {
+    transactions: Vec<TransactionWithProduct>,
+    /// Pass as `cursor` for the next page; null on the last page
+    next_cursor: Option<String>,
+    /// Transactions matching the filters across all pages, when `include_total` is set
+    total_count: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct Product {
+    name: String,
+    sku: String,
+    material: Material,
+    variant_label: String,
+    image_url: Option<String>,
+    /// When Pure stopped listing this variant; null while it is listed
+    delisted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct ProductTransaction {
+    sku: String,
+    variant_label: String,
+    event_time: DateTime<Utc>,
+    quantity: i32,
+    price: Money,
+    spot_premium_percentage: f64,
+    spot_premium_dollar: Money,
+    event_type: Option<EventType>,
+    event_type_confidence: Option<f64>,
+    event_type_source: Option<String>,
+    upstream_event: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProductDetailsResponse {
+    variants: Vec<Product>,
+    transactions: Vec<ProductTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarketHistoryParams {
+    variant_id: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct MarketSnapshotPoint {
+    pure_variant_id: String,
+    variant_label: String,
+    captured_at: DateTime<Utc>,
+    highest_offer_spot_premium: Option<f64>,
+    lowest_listing_spot_premium: Option<f64>,
+    spread: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct MarketHistoryResponse {
+    snapshots: Vec<MarketSnapshotPoint>,
+}
+
+/// Width of a candle
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum CandleInterval {
+    #[serde(rename = "1h")]
+    Hour,
+    #[default]
+    #[serde(rename = "1d")]
+    Day,
+    /// Weeks start on Monday
+    #[serde(rename = "1w")]
+    Week,
+    #[serde(rename = "1M")]
+    Month,
+}
+
+impl CandleInterval {
+    /// The `date_trunc` field that buckets by this interval
+    fn date_trunc_field(self) -> &'static str {
+        match self {
+            CandleInterval::Hour => "hour",
+            CandleInterval::Day => "day",
+            CandleInterval::Week => "week",
+            CandleInterval::Month => "month",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CandlesParams {
+    #[serde(default)]
+    interval: CandleInterval,
+    variant_id: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// IANA time zone whose days, weeks and months the candles follow
+    tz: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct Candle {
+    /// Start of the bucket, midnight in `tz` for daily and longer intervals
+    bucket_start: DateTime<Utc>,
+    open_price: Money,
+    high_price: Money,
+    low_price: Money,
+    close_price: Money,
+    open_premium: f64,
+    high_premium: f64,
+    low_premium: f64,
+    close_premium: f64,
+    /// Traded value, `price * quantity`, in cents
+    volume: Money,
+    quantity: i64,
+    trade_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct CandlesResponse {
+    tz: String,
+    candles: Vec<Candle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyticsParams {
+    variant_id: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Trades whose side was classified with less confidence still count
+    /// toward `all`, but toward neither `buy` nor `sell`; same as on `/products/stats`
+    min_confidence: Option<f64>,
+}
+
+/// Price level of a set of trades; averages are weighted by quantity, and the
+/// premium percentiles taken over trades
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct PriceAnalytics {
+    trade_count: i64,
+    quantity: i64,
+    /// In cents
+    volume: Option<Money>,
+    /// Volume-weighted average price, in cents
+    vwap: Option<f64>,
+    vw_premium_percentage: Option<f64>,
+    /// Volume-weighted average spot premium per unit, in cents
+    vw_premium_dollar: Option<f64>,
+    median_premium_percentage: Option<f64>,
+    p10_premium_percentage: Option<f64>,
+    p90_premium_percentage: Option<f64>,
+}
+
+#[derive(Debug, FromRow)]
+struct PriceAnalyticsRow {
+    pure_variant_id: String,
+    variant_label: String,
+    /// Whether this row covers every trade rather than one side
+    is_total: bool,
+    side: Option<EventType>,
+    #[sqlx(flatten)]
+    analytics: PriceAnalytics,
+}
+
+#[derive(Debug, Serialize)]
+struct VariantAnalytics {
+    pure_variant_id: String,
+    variant_label: String,
+    all: PriceAnalytics,
+    /// Null when there were no buys in the window
+    buy: Option<PriceAnalytics>,
+    sell: Option<PriceAnalytics>,
+}
+
+#[derive(Debug, Serialize)]
+struct AnalyticsResponse {
+    variants: Vec<VariantAnalytics>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProductHistoryParams {
+    variant_id: Option<String>,
+}
+
+/// A version of a variant's catalog fields, with the previous version's values
+#[derive(Debug, FromRow)]
+struct ProductRevisionRow {
+    pure_variant_id: String,
+    name: String,
+    sku: String,
+    material: Material,
+    variant_label: String,
+    image_url: Option<String>,
+    changed_fields: Vec<String>,
+    valid_from: DateTime<Utc>,
+    valid_to: Option<DateTime<Utc>>,
+    previous_name: Option<String>,
+    previous_sku: Option<String>,
+    previous_material: Option<Material>,
+    previous_variant_label: Option<String>,
+    previous_image_url: Option<String>,
+}
+
+impl ProductRevisionRow {
+    fn into_revision(self) -> ProductRevision {
+        let changes = self.changed_fields
+            .iter()
+            .map(|field| {
+                let (old, new) = match field.as_str() {
+                    "name" => (self.previous_name.clone(), Some(self.name.clone())),
+                    "sku" => (self.previous_sku.clone(), Some(self.sku.clone())),
+                    "material" => (
+                        self.previous_material.map(|m| m.as_str().to_string()),
+                        Some(self.material.as_str().to_string()),
+                    ),
+                    "variant_label" => (self.previous_variant_label.clone(), Some(self.variant_label.clone())),
+                    "image_url" => (self.previous_image_url.clone(), self.image_url.clone()),
+                    _ => (None, None),
+                };
+                FieldChange { field: field.clone(), old, new }
+            })
+            .collect();
+
+        ProductRevision {
+            pure_variant_id: self.pure_variant_id,
+            valid_from: self.valid_from,
+            valid_to: self.valid_to,
+            name: self.name,
+            sku: self.sku,
+            material: self.material,
+            variant_label: self.variant_label,
+            image_url: self.image_url,
+            changes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FieldChange {
+    field: String,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProductRevision {
+    pure_variant_id: String,
+    valid_from: DateTime<Utc>,
+    /// When this version was replaced; null for the current one
+    valid_to: Option<DateTime<Utc>>,
+    name: String,
+    sku: String,
+    material: Material,
+    variant_label: String,
+    image_url: Option<String>,
+    /// Fields that differ from the previous version; empty for the first
+    changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProductHistoryResponse {
+    revisions: Vec<ProductRevision>,
+}
+
+/// Whether a product is still listed on Pure
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ListingStatus {
+    /// At least one variant is listed
+    Active,
+    /// Every variant has been delisted
+    Delisted,
+}
+
+/// Period `/products/stats` aggregates over, ending now
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum StatsWindow {
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+    #[serde(rename = "90d")]
+    Quarter,
+    #[serde(rename = "all")]
+    All,
+}
+
+impl StatsWindow {
+    /// How far back the window reaches; `None` for all of history
+    fn duration(self) -> Option<TimeDelta> {
+        match self {
+            StatsWindow::Day => Some(TimeDelta::hours(24)),
+            StatsWindow::Week => Some(TimeDelta::days(7)),
+            StatsWindow::Month => Some(TimeDelta::days(30)),
+            StatsWindow::Quarter => Some(TimeDelta::days(90)),
+            StatsWindow::All => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProductStatsParams {
+    material: Option<Material>,
+    /// Trades whose side was classified with less confidence still count toward
+    /// the transaction count and total volume, but toward neither the buy nor
+    /// the sell figures; same as on `/product/:id/analytics`
+    min_confidence: Option<f64>,
+    status: Option<ListingStatus>,
+    /// Shorthand for a `from` that far back and a `to` of now
+    window: Option<StatsWindow>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Also aggregate the window of the same length just before this one
+    #[serde(default)]
+    compare: bool,
+}
+
+/// Trading activity of a product over one window
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct StatsMetrics {
+    transaction_count: i64,
+    buy_count: i64,
+    sell_count: i64,
+    buy_sell_ratio: Option<f64>,
+    total_volume: Option<Money>,
+    total_buy_quantity: Option<i64>,
+    total_sell_quantity: Option<i64>,
+    total_buy_amount: Option<Money>,
+    total_sell_amount: Option<Money>,
+}
+
+impl StatsMetrics {
+    fn percent_change_from(&self, previous: &StatsMetrics) -> StatsChanges {
+        let change = |current: Option<i64>, previous: Option<i64>| {
+            percent_change(current.unwrap_or(0) as f64, previous.unwrap_or(0) as f64)
+        };
+
+        StatsChanges {
+            transaction_count: change(Some(self.transaction_count), Some(previous.transaction_count)),
+            buy_count: change(Some(self.buy_count), Some(previous.buy_count)),
+            sell_count: change(Some(self.sell_count), Some(previous.sell_count)),
+            buy_sell_ratio: self.buy_sell_ratio
+                .zip(previous.buy_sell_ratio)
+                .and_then(|(current, previous)| percent_change(current, previous)),
+            total_volume: change(self.total_volume.map(Money::cents), previous.total_volume.map(Money::cents)),
+            total_buy_quantity: change(self.total_buy_quantity, previous.total_buy_quantity),
+            total_sell_quantity: change(self.total_sell_quantity, previous.total_sell_quantity),
+            total_buy_amount: change(self.total_buy_amount.map(Money::cents), previous.total_buy_amount.map(Money::cents)),
+            total_sell_amount: change(self.total_sell_amount.map(Money::cents), previous.total_sell_amount.map(Money::cents)),
+        }
+    }
+}
+
+/// Percent change of each metric against the previous window; null where the
+/// previous value was zero or missing
+#[derive(Debug, Serialize, Deserialize)]
+struct StatsChanges {
+    transaction_count: Option<f64>,
+    buy_count: Option<f64>,
+    sell_count: Option<f64>,
+    buy_sell_ratio: Option<f64>,
+    total_volume: Option<f64>,
+    total_buy_quantity: Option<f64>,
+    total_sell_quantity: Option<f64>,
+    total_buy_amount: Option<f64>,
+    total_sell_amount: Option<f64>,
+}
+
+fn percent_change(current: f64, previous: f64) -> Option<f64> {
+    (previous != 0.0).then(|| (current - previous) / previous * 100.0)
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct ProductStats {
+    pure_product_id: String,
+    material: Material,
+    name: String,
+    sku: String,
+    image_url: Option<String>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    metrics: StatsMetrics,
+    /// When the last variant was delisted; null while any variant is listed
+    delisted_at: Option<DateTime<Utc>>,
+    delisted_variant_count: i64,
+    /// The same metrics over the previous window, with `compare`
+    #[sqlx(skip)]
+    previous: Option<StatsMetrics>,
+    #[sqlx(skip)]
+    percent_change: Option<StatsChanges>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProductStatsResponse {
+    /// Window the stats cover; null ends are unbounded
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Window `previous` covers, with `compare`
+    previous_from: Option<DateTime<Utc>>,
+    previous_to: Option<DateTime<Utc>>,
+    products: Vec<ProductStats>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncRunsParams {
+    kind: Option<SyncRunKind>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct SyncRun {
+    id: i64,
+    kind: String,
+    status: String,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    products_attempted: i32,
+    products_failed: i32,
+    rows_inserted: i64,
+    rows_updated: i64,
+    rows_unchanged: i64,
+    rows_written: i64,
+    error_summary: Option<String>,
+    #[sqlx(skip)]
+    failures: Vec<SyncRunFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct SyncRunFailure {
+    #[serde(skip)]
+    sync_run_id: i64,
+    pure_product_id: Option<String>,
+    pure_variant_id: Option<String>,
+    error_kind: Option<String>,
+    error: String,
+    occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncRunsResponse {
+    runs: Vec<SyncRun>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+struct SyncKindStatus {
+    kind: String,
+    last_run_id: i64,
+    last_status: String,
+    last_started_at: DateTime<Utc>,
+    last_finished_at: Option<DateTime<Utc>>,
+    last_completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncStatusResponse {
+    /// When transaction data was last fully refreshed
+    data_as_of: Option<DateTime<Utc>>,
+    kinds: Vec<SyncKindStatus>,
+}
+
+/// The API's routes, backed by `pool`
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/transactions", get(get_transactions))
+        .route("/products/stats", get(get_product_stats))
+        .route("/product/:product_id", get(get_product))
+        .route("/product/:product_id/market-history", get(get_market_history))
+        .route("/product/:product_id/history", get(get_product_history))
+        .route("/product/:product_id/candles", get(get_candles))
+        .route("/product/:product_id/analytics", get(get_analytics))
+        .route("/sync/runs", get(get_sync_runs))
+        .route("/sync/status", get(get_sync_status))
+        .fallback(|| async { ApiError::NotFound("No such endpoint".to_string()) })
+        .with_state(pool)
+        .layer(middleware::from_fn(error::assign_request_id))
+}
+
+async fn health_check() -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "service": "pure-trading-api"
+    }))
+}
+
+/// Filters shared by the page and total count queries, binding $1 to $12
+const TRANSACTION_FILTERS: &str = r#"
+        ($1::material IS NULL OR p.material = $1)
+        AND ($2::VARCHAR IS NULL OR t.pure_product_id = $2)
+        AND ($3::VARCHAR IS NULL OR t.pure_variant_id = $3)
+        AND ($4::event_type IS NULL OR t.event_type = $4)
+        AND ($5::TIMESTAMPTZ IS NULL OR t.event_time >= $5)
+        AND ($6::TIMESTAMPTZ IS NULL OR t.event_time < $6)
+        AND ($7::BIGINT IS NULL OR t.price >= $7)
+        AND ($8::BIGINT IS NULL OR t.price <= $8)
+        AND ($9::FLOAT8 IS NULL OR t.spot_premium_percentage >= $9)
+        AND ($10::FLOAT8 IS NULL OR t.spot_premium_percentage <= $10)
+        AND ($11::INT IS NULL OR t.quantity >= $11)
+        AND ($12::INT IS NULL OR t.quantity <= $12)
+"#;
+
+fn bind_transaction_filters<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    params: &'q TransactionsParams,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(params.material)
+        .bind(params.product_id.as_deref())
+        .bind(params.variant_id.as_deref())
+        .bind(params.event_type)
+        .bind(params.from)
+        .bind(params.to)
+        .bind(params.min_price)
+        .bind(params.max_price)
+        .bind(params.min_premium)
+        .bind(params.max_premium)
+        .bind(params.min_quantity)
+        .bind(params.max_quantity)
+}
+
+async fn get_transactions(
+    State(pool): State<PgPool>,
+    ApiQuery(params): ApiQuery<TransactionsParams>,
+) -> Result<Json<TransactionsResponse>, ApiError> {
+    let cursor = match params.cursor.as_deref() {
+        Some(cursor) => Some(
+            TransactionCursor::decode(cursor)
+                .ok_or_else(|| ApiError::BadRequest(format!("Invalid cursor: {}", cursor)))?,
+        ),
+        None => None,
+    };
+    check_time_range(params.from, params.to)?;
+    let limit = params.limit.unwrap_or(DEFAULT_TRANSACTIONS_LIMIT).clamp(1, MAX_TRANSACTIONS_LIMIT);
+
+    // One row past the page tells whether there is another page
+    let page_query = format!(
+        r#"
+        SELECT
+            t.id,
+            p.pure_product_id,
+            p.pure_variant_id,
+            p.name,
+            p.sku,
+            p.material,
+            p.variant_label,
+            p.image_url,
+            t.event_time,
+            t.quantity,
+            t.price,
+            t.spot_premium_percentage::FLOAT8 as spot_premium_percentage,
+            t.spot_premium_dollar,
+            t.event_type,
+            t.event_type_confidence,
+            t.event_type_source,
+            t.upstream_event
+        FROM transactions t
+        INNER JOIN products p ON t.product_id = p.id
+        WHERE {}
+            AND ($13::TIMESTAMPTZ IS NULL OR (t.event_time, t.id) < ($13, $14::BIGINT))
+        ORDER BY t.event_time DESC, t.id DESC
+        LIMIT $15
+        "#,
+        TRANSACTION_FILTERS
+    );
+    let mut transactions = bind_transaction_filters(sqlx::query_as::<_, TransactionWithProduct>(&page_query), &params)
+        .bind(cursor.map(|c| c.event_time))
+        .bind(cursor.map(|c| c.id))
+        .bind(limit + 1)
+        .fetch_all(&pool)
+        .await?;
+
+    let next_cursor = if transactions.len() as i64 > limit {
+        transactions.truncate(limit as usize);
+        transactions.last().map(|t| TransactionCursor { event_time: t.event_time, id: t.id }.encode())
+    } else {
+        None
+    };
+
+    let total_count = if params.include_total {
+        let count_query = format!(
+            r#"
+            SELECT COUNT(*)
+            FROM transactions t
+            INNER JOIN products p ON t.product_id = p.id
+            WHERE {}
+            "#,
+            TRANSACTION_FILTERS
+        );
+        let (count,) = bind_transaction_filters(sqlx::query_as::<_, (i64,)>(&count_query), &params)
+            .fetch_one(&pool)
+            .await?;
+        Some(count)
+    } else {
+        None
+    };
+
+    Ok(Json(TransactionsResponse { transactions, next_cursor, total_count }))
+}
+
+/// Rejects a `from`/`to` range that ends before it starts
+fn check_time_range(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<(), ApiError> {
+    match (from, to) {
+        (Some(from), Some(to)) if from > to => Err(ApiError::BadRequest(format!("from ({}) is after to ({})", from, to))),
+        _ => Ok(()),
+    }
+}
+
+fn check_min_confidence(min_confidence: Option<f64>) -> Result<(), ApiError> {
+    match min_confidence {
+        Some(min_confidence) if !(0.0..=1.0).contains(&min_confidence) => Err(ApiError::BadRequest(
+            format!("min_confidence must be between 0 and 1, got {}", min_confidence),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn unknown_product(product_id: &str) -> ApiError {
+    ApiError::NotFound(format!("No product with ID {}", product_id))
+}
+
+/// Fails with a 404 unless some variant of `product_id` has been stored
+async fn ensure_product_exists(pool: &PgPool, product_id: &str) -> Result<(), ApiError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM products WHERE pure_product_id = $1)")
+        .bind(product_id)
+        .fetch_one(pool)
+        .await?;
+
+    if exists { Ok(()) } else { Err(unknown_product(product_id)) }
+}
+
+async fn get_product(
+    State(pool): State<PgPool>,
+    ApiPath(product_id): ApiPath<String>,
+) -> Result<Json<ProductDetailsResponse>, ApiError> {
+    // Fetch all variants for this product
+    let variants = sqlx::query_as::<_, Product>(
+        r#"
+        SELECT
+            name,
+            sku,
+            material,
+            variant_label,
+            image_url,
+            delisted_at
+        FROM products
+        WHERE pure_product_id = $1
+        ORDER BY variant_label
+        "#
+    )
+    .bind(&product_id)
+    .fetch_all(&pool)
+    .await?;
+
+    if variants.is_empty() {
+        return Err(unknown_product(&product_id));
+    }
+
+    // Fetch all transactions for all variants of this product
+    let transactions = sqlx::query_as::<_, ProductTransaction>(
+        r#"
+        SELECT
+            p.sku,
+            p.variant_label,
+            t.event_time,
+            t.quantity,
+            t.price,
+            t.spot_premium_percentage::FLOAT8 as spot_premium_percentage,
+            t.spot_premium_dollar,
+            t.event_type,
+            t.event_type_confidence,
+            t.event_type_source,
+            t.upstream_event
+        FROM transactions t
+        INNER JOIN products p ON t.product_id = p.id
+        WHERE p.pure_product_id = $1
+        ORDER BY t.event_time DESC
+        "#
+    )
+    .bind(&product_id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(ProductDetailsResponse {
+        variants,
+        transactions,
+    }))
+}
+
+async fn get_market_history(
+    State(pool): State<PgPool>,
+    ApiPath(product_id): ApiPath<String>,
+    ApiQuery(params): ApiQuery<MarketHistoryParams>,
+) -> Result<Json<MarketHistoryResponse>, ApiError> {
+    check_time_range(params.from, params.to)?;
+    ensure_product_exists(&pool, &product_id).await?;
+
+    // Bid/ask premium time series, optionally narrowed to one variant and a time range
+    let snapshots = sqlx::query_as::<_, MarketSnapshotPoint>(
+        r#"
+        SELECT
+            s.pure_variant_id,
+            p.variant_label,
+            s.captured_at,
+            s.highest_offer_spot_premium,
+            s.lowest_listing_spot_premium,
+            s.lowest_listing_spot_premium - s.highest_offer_spot_premium as spread
+        FROM market_snapshots s
+        INNER JOIN products p ON s.product_id = p.id
+        WHERE s.pure_product_id = $1
+            AND ($2::TEXT IS NULL OR s.pure_variant_id = $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR s.captured_at >= $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR s.captured_at < $4)
+        ORDER BY s.pure_variant_id, s.captured_at
+        "#
+    )
+    .bind(&product_id)
+    .bind(&params.variant_id)
+    .bind(params.from)
+    .bind(params.to)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(MarketHistoryResponse { snapshots }))
+}
+
+async fn get_candles(
+    State(pool): State<PgPool>,
+    ApiPath(product_id): ApiPath<String>,
+    ApiQuery(params): ApiQuery<CandlesParams>,
+) -> Result<Json<CandlesResponse>, ApiError> {
+    check_time_range(params.from, params.to)?;
+    let tz = params.tz.unwrap_or_else(|| "UTC".to_string());
+
+    let known_tz: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+        .bind(&tz)
+        .fetch_one(&pool)
+        .await?;
+    if !known_tz {
+        return Err(ApiError::BadRequest(format!("Unknown time zone: {}", tz)));
+    }
+
+    ensure_product_exists(&pool, &product_id).await?;
+
+    // Trades bucketed by the interval as it falls in tz; open and close are the
+    // bucket's first and last trades
+    let candles = sqlx::query_as::<_, Candle>(
+        r#"
+        SELECT
+            date_trunc($2, t.event_time, $3) as bucket_start,
+            (ARRAY_AGG(t.price ORDER BY t.event_time, t.id))[1] as open_price,
+            MAX(t.price) as high_price,
+            MIN(t.price) as low_price,
+            (ARRAY_AGG(t.price ORDER BY t.event_time DESC, t.id DESC))[1] as close_price,
+            (ARRAY_AGG(t.spot_premium_percentage ORDER BY t.event_time, t.id))[1]::FLOAT8 as open_premium,
+            MAX(t.spot_premium_percentage)::FLOAT8 as high_premium,
+            MIN(t.spot_premium_percentage)::FLOAT8 as low_premium,
+            (ARRAY_AGG(t.spot_premium_percentage ORDER BY t.event_time DESC, t.id DESC))[1]::FLOAT8 as close_premium,
+            SUM(t.price * t.quantity)::BIGINT as volume,
+            SUM(t.quantity)::BIGINT as quantity,
+            COUNT(*) as trade_count
+        FROM transactions t
+        WHERE t.pure_product_id = $1
+            AND ($4::TEXT IS NULL OR t.pure_variant_id = $4)
+            AND ($5::TIMESTAMPTZ IS NULL OR t.event_time >= $5)
+            AND ($6::TIMESTAMPTZ IS NULL OR t.event_time < $6)
+        GROUP BY 1
+        ORDER BY 1
+        "#
+    )
+    .bind(&product_id)
+    .bind(params.interval.date_trunc_field())
+    .bind(&tz)
+    .bind(&params.variant_id)
+    .bind(params.from)
+    .bind(params.to)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(CandlesResponse { tz, candles }))
+}
+
+async fn get_analytics(
+    State(pool): State<PgPool>,
+    ApiPath(product_id): ApiPath<String>,
+    ApiQuery(params): ApiQuery<AnalyticsParams>,
+) -> Result<Json<AnalyticsResponse>, ApiError> {
+    check_time_range(params.from, params.to)?;
+    check_min_confidence(params.min_confidence)?;
+    ensure_product_exists(&pool, &product_id).await?;
+
+    // One row for every trade of each variant in the window, then one per side;
+    // variants without trades still get their (empty) total row
+    let rows = sqlx::query_as::<_, PriceAnalyticsRow>(
+        r#"
+        WITH trades AS (
+            SELECT
+                p.id as variant_id,
+                p.pure_variant_id,
+                p.variant_label,
+                CASE
+                    WHEN $5::FLOAT8 IS NULL OR t.event_type_confidence >= $5 THEN t.event_type
+                END as side,
+                t.id,
+                t.price,
+                t.quantity,
+                t.spot_premium_percentage::FLOAT8 as premium_percentage,
+                t.spot_premium_dollar
+            FROM products p
+            LEFT JOIN transactions t ON t.product_id = p.id
+                AND ($3::TIMESTAMPTZ IS NULL OR t.event_time >= $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR t.event_time < $4)
+            WHERE p.pure_product_id = $1
+                AND ($2::TEXT IS NULL OR p.pure_variant_id = $2)
+        )
+        SELECT
+            pure_variant_id,
+            variant_label,
+            GROUPING(side) = 1 as is_total,
+            side,
+            COUNT(id) as trade_count,
+            COALESCE(SUM(quantity), 0)::BIGINT as quantity,
+            SUM(price * quantity)::BIGINT as volume,
+            SUM(price * quantity)::FLOAT8 / NULLIF(SUM(quantity), 0) as vwap,
+            SUM(premium_percentage * quantity) / NULLIF(SUM(quantity), 0) as vw_premium_percentage,
+            SUM(spot_premium_dollar * quantity)::FLOAT8 / NULLIF(SUM(quantity), 0) as vw_premium_dollar,
+            PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY premium_percentage) as median_premium_percentage,
+            PERCENTILE_CONT(0.1) WITHIN GROUP (ORDER BY premium_percentage) as p10_premium_percentage,
+            PERCENTILE_CONT(0.9) WITHIN GROUP (ORDER BY premium_percentage) as p90_premium_percentage
+        FROM trades
+        GROUP BY GROUPING SETS (
+            (variant_id, pure_variant_id, variant_label),
+            (variant_id, pure_variant_id, variant_label, side)
+        )
+        ORDER BY variant_label, variant_id, is_total DESC
+        "#
+    )
+    .bind(&product_id)
+    .bind(&params.variant_id)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(params.min_confidence)
+    .fetch_all(&pool)
+    .await?;
+
+    let mut variants: Vec<VariantAnalytics> = Vec::new();
+    for row in rows {
+        if row.is_total {
+            variants.push(VariantAnalytics {
+                pure_variant_id: row.pure_variant_id,
+                variant_label: row.variant_label,
+                all: row.analytics,
+                buy: None,
+                sell: None,
+            });
+        } else if let Some(variant) = variants.last_mut() {
+            match row.side {
+                Some(EventType::Buy) => variant.buy = Some(row.analytics),
+                Some(EventType::Sell) => variant.sell = Some(row.analytics),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Json(AnalyticsResponse { variants }))
+}
+
+async fn get_product_history(
+    State(pool): State<PgPool>,
+    ApiPath(product_id): ApiPath<String>,
+    ApiQuery(params): ApiQuery<ProductHistoryParams>,
+) -> Result<Json<ProductHistoryResponse>, ApiError> {
+    ensure_product_exists(&pool, &product_id).await?;
+
+    // Every version of each variant's catalog fields, newest first
+    let rows = sqlx::query_as::<_, ProductRevisionRow>(
+        r#"
+        SELECT
+            pure_variant_id,
+            name,
+            sku,
+            material,
+            variant_label,
+            image_url,
+            changed_fields,
+            valid_from,
+            valid_to,
+            LAG(name) OVER variant_versions as previous_name,
+            LAG(sku) OVER variant_versions as previous_sku,
+            LAG(material) OVER variant_versions as previous_material,
+            LAG(variant_label) OVER variant_versions as previous_variant_label,
+            LAG(image_url) OVER variant_versions as previous_image_url
+        FROM product_history
+        WHERE pure_product_id = $1
+            AND ($2::TEXT IS NULL OR pure_variant_id = $2)
+        WINDOW variant_versions AS (PARTITION BY product_id ORDER BY valid_from, id)
+        ORDER BY valid_from DESC, id DESC
+        "#
+    )
+    .bind(&product_id)
+    .bind(&params.variant_id)
+    .fetch_all(&pool)
+    .await?;
+
+    let revisions = rows.into_iter().map(ProductRevisionRow::into_revision).collect();
+
+    Ok(Json(ProductHistoryResponse { revisions }))
+}
+
+async fn get_product_stats(
+    State(pool): State<PgPool>,
+    ApiQuery(params): ApiQuery<ProductStatsParams>,
+) -> Result<Json<ProductStatsResponse>, ApiError> {
+    check_min_confidence(params.min_confidence)?;
+    let (from, to) = resolve_stats_window(&params, Utc::now())?;
+
+    let mut products = fetch_product_stats(&pool, &params, from, to).await?;
+
+    let (previous_from, previous_to) = if params.compare {
+        let Some(from) = from else {
+            return Err(ApiError::BadRequest(
+                "compare needs a bounded window: a window other than all, or a from".to_string(),
+            ));
+        };
+        let to = to.unwrap_or_else(Utc::now);
+        let previous_from = from - (to - from);
+
+        let mut previous: HashMap<String, StatsMetrics> = fetch_product_stats(&pool, &params, Some(previous_from), Some(from))
+            .await?
+            .into_iter()
+            .map(|stats| (stats.pure_product_id, stats.metrics))
+            .collect();
+        for product in &mut products {
+            if let Some(metrics) = previous.remove(&product.pure_product_id) {
+                product.percent_change = Some(product.metrics.percent_change_from(&metrics));
+                product.previous = Some(metrics);
+            }
+        }
+
+        (Some(previous_from), Some(from))
+    } else {
+        (None, None)
+    };
+
+    Ok(Json(ProductStatsResponse { from, to, previous_from, previous_to, products }))
+}
+
+/// `from` and `to` bounds, either of which may be open
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// The range the stats cover, given either a `window` or explicit bounds
+fn resolve_stats_window(params: &ProductStatsParams, now: DateTime<Utc>) -> Result<TimeRange, ApiError> {
+    match params.window {
+        Some(_) if params.from.is_some() || params.to.is_some() => {
+            Err(ApiError::BadRequest("window can't be combined with from or to".to_string()))
+        }
+        Some(window) => Ok(match window.duration() {
+            Some(duration) => (Some(now - duration), Some(now)),
+            None => (None, None),
+        }),
+        None => {
+            check_time_range(params.from, params.to)?;
+            Ok((params.from, params.to))
+        }
+    }
+}
+
+async fn fetch_product_stats(
+    pool: &PgPool,
+    params: &ProductStatsParams,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<ProductStats>, ApiError> {
+    // Transactions outside the window are left out; those classified with less
+    // than min_confidence are counted, but on neither side
+    let products = sqlx::query_as::<_, ProductStats>(
+        r#"
+        SELECT
+            p.pure_product_id,
+            p.material,
+            p.name,
+            MIN(p.sku) as sku,
+            MIN(p.image_url) as image_url,
+            COUNT(t.id) as transaction_count,
+            COUNT(t.id) FILTER (WHERE t.side = $1) as buy_count,
+            COUNT(t.id) FILTER (WHERE t.side = $2) as sell_count,
+            CASE
+                WHEN COUNT(t.id) FILTER (WHERE t.side = $2) > 0
+                THEN (COUNT(t.id) FILTER (WHERE t.side = $1))::FLOAT8 / (COUNT(t.id) FILTER (WHERE t.side = $2))::FLOAT8
+                ELSE NULL
+            END as buy_sell_ratio,
+            SUM(t.price * t.quantity)::BIGINT as total_volume,
+            SUM(t.quantity) FILTER (WHERE t.side = $1) as total_buy_quantity,
+            SUM(t.quantity) FILTER (WHERE t.side = $2) as total_sell_quantity,
+            SUM(t.price * t.quantity) FILTER (WHERE t.side = $1)::BIGINT as total_buy_amount,
+            SUM(t.price * t.quantity) FILTER (WHERE t.side = $2)::BIGINT as total_sell_amount,
+            CASE
+                WHEN BOOL_AND(p.delisted_at IS NOT NULL) THEN MAX(p.delisted_at)
+            END as delisted_at,
+            COUNT(DISTINCT p.id) FILTER (WHERE p.delisted_at IS NOT NULL) as delisted_variant_count
+        FROM products p
+        LEFT JOIN (
+            SELECT
+                product_id,
+                id,
+                price,
+                quantity,
+                CASE
+                    WHEN $4::FLOAT8 IS NULL OR event_type_confidence >= $4 THEN event_type
+                END as side
+            FROM transactions
+            WHERE ($6::TIMESTAMPTZ IS NULL OR event_time >= $6)
+                AND ($7::TIMESTAMPTZ IS NULL OR event_time < $7)
+        ) t ON p.id = t.product_id
+        WHERE ($3::material IS NULL OR p.material = $3)
+        GROUP BY p.pure_product_id, p.material, p.name
+        HAVING ($5::BOOL IS NULL OR BOOL_AND(p.delisted_at IS NOT NULL) = $5)
+        ORDER BY total_volume DESC NULLS LAST
+        "#
+    )
+    .bind(EventType::Buy)
+    .bind(EventType::Sell)
+    .bind(params.material)
+    .bind(params.min_confidence)
+    .bind(params.status.map(|status| matches!(status, ListingStatus::Delisted)))
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(products)
+}
+
+async fn get_sync_runs(
+    State(pool): State<PgPool>,
+    ApiQuery(params): ApiQuery<SyncRunsParams>,
+) -> Result<Json<SyncRunsResponse>, ApiError> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    // Most recent runs first, optionally narrowed to one kind
+    let mut runs = sqlx::query_as::<_, SyncRun>(
+        r#"
+        SELECT
+            id,
+            kind,
+            status,
+            started_at,
+            finished_at,
+            products_attempted,
+            products_failed,
+            rows_inserted,
+            rows_updated,
+            rows_unchanged,
+            rows_inserted + rows_updated as rows_written,
+            error_summary
+        FROM sync_runs
+        WHERE ($1::TEXT IS NULL OR kind = $1)
+        ORDER BY started_at DESC
+        LIMIT $2
+        "#
+    )
+    .bind(params.kind.as_ref().map(SyncRunKind::as_str))
+    .bind(limit)
+    .fetch_all(&pool)
+    .await?;
+
+    let run_ids: Vec<i64> = runs.iter().map(|run| run.id).collect();
+    let failures = sqlx::query_as::<_, SyncRunFailure>(
+        r#"
+        SELECT sync_run_id, pure_product_id, pure_variant_id, error_kind, error, occurred_at
+        FROM sync_run_failures
+        WHERE sync_run_id = ANY($1)
+        ORDER BY occurred_at
+        "#
+    )
+    .bind(&run_ids)
+    .fetch_all(&pool)
+    .await?;
+
+    let mut failures_by_run: HashMap<i64, Vec<SyncRunFailure>> = HashMap::new();
+    for failure in failures {
+        failures_by_run.entry(failure.sync_run_id).or_default().push(failure);
+    }
+    for run in &mut runs {
+        run.failures = failures_by_run.remove(&run.id).unwrap_or_default();
+    }
+
+    Ok(Json(SyncRunsResponse { runs }))
+}
+
+async fn get_sync_status(State(pool): State<PgPool>) -> Result<Json<SyncStatusResponse>, ApiError> {
+    // Latest run of each kind, alongside when that kind last ran to completion
+    let kinds = sqlx::query_as::<_, SyncKindStatus>(
+        r#"
+        SELECT DISTINCT ON (r.kind)
+            r.kind,
+            r.id as last_run_id,
+            r.status as last_status,
+            r.started_at as last_started_at,
+            r.finished_at as last_finished_at,
+            (
+                SELECT MAX(c.finished_at)
+                FROM sync_runs c
+                WHERE c.kind = r.kind AND c.status = ANY($1)
+            ) as last_completed_at
+        FROM sync_runs r
+        ORDER BY r.kind, r.started_at DESC
+        "#
+    )
+    .bind(SyncRunStatus::COMPLETED.map(|status| status.as_str()))
+    .fetch_all(&pool)
+    .await?;
+
+    let data_as_of = kinds
+        .iter()
+        .find(|status| status.kind == SyncRunKind::Transactions.as_str())
+        .and_then(|status| status.last_completed_at);
+
+    Ok(Json(SyncStatusResponse { data_as_of, kinds }))
+}
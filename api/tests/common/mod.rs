@@ -0,0 +1,139 @@
+use anyhow::Result;
+use ingestion::config::Config;
+use ingestion::event_type::TradeClassifier;
+use ingestion::mock_pure_api::{client_config, Fixtures, MockPureApi, DEFAULT_FIXTURES_DIR};
+use ingestion::pure_api::PureApiClient;
+use ingestion::scheduler::PollScheduler;
+use ingestion::sync;
+use serde_json::Value;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static NEXT_SCHEMA: AtomicU32 = AtomicU32::new(0);
+
+/// A migrated schema of its own, the mock Pure API and the API server, for one test
+pub struct TestContext {
+    pub pool: PgPool,
+    pub mock: MockPureApi,
+    pub config: Config,
+    pub client: PureApiClient,
+    api_url: String,
+    http: reqwest::Client,
+    admin: PgPool,
+    schema: String,
+}
+
+impl TestContext {
+    /// Sets everything up, or returns `None` so the test can skip itself when
+    /// `TEST_DATABASE_URL` isn't set
+    pub async fn new() -> Option<Self> {
+        let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return None;
+        };
+        Some(Self::connect(&database_url).await.expect("failed to set up test context"))
+    }
+
+    async fn connect(database_url: &str) -> Result<Self> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos();
+        let schema = format!(
+            "it_{}_{}_{}",
+            std::process::id(), NEXT_SCHEMA.fetch_add(1, Ordering::Relaxed), nanos
+        );
+
+        let admin = PgPoolOptions::new().max_connections(1).connect(database_url).await?;
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await?;
+
+        // Every connection resolves unqualified names, including the enums and
+        // the migrations table, in the test's own schema
+        let options = PgConnectOptions::from_str(database_url)?.options([("search_path", schema.as_str())]);
+        let pool = PgPoolOptions::new().max_connections(5).connect_with(options).await?;
+        sqlx::migrate!("../migrations").run(&pool).await?;
+
+        let mock = MockPureApi::new(Fixtures::load(DEFAULT_FIXTURES_DIR)?);
+        let config = client_config(&mock.spawn().await?, database_url);
+        let client = PureApiClient::new(&config)?;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let api_url = format!("http://{}", listener.local_addr()?);
+        let app = api::router(pool.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Ok(Self { pool, mock, config, client, api_url, http: reqwest::Client::new(), admin, schema })
+    }
+
+    pub async fn sync_products(&self) -> Result<()> {
+        sync::sync_products(&self.pool, &self.client, self.config.product_insert_batch_size).await
+    }
+
+    pub async fn sync_transactions(&self) -> Result<()> {
+        let classifier = TradeClassifier::from_config(&self.config)?;
+        let scheduler = PollScheduler::from_config(&self.config)?;
+        sync::sync_transactions(
+            &self.pool,
+            &self.client,
+            &classifier,
+            &scheduler,
+            self.config.transaction_insert_batch_size,
+            self.config.activity_fetch_concurrency,
+        ).await
+    }
+
+    /// Makes every variant due for an activity poll again
+    pub async fn make_all_variants_due(&self) {
+        sqlx::query("UPDATE variant_sync_state SET next_poll_at = NOW() - INTERVAL '1 second'")
+            .execute(&self.pool)
+            .await
+            .unwrap();
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Classifies every trade with the raw upstream event `upstream_event` as
+    /// `event_type`, as if the premium heuristic had
+    pub async fn set_side(&self, upstream_event: &str, event_type: &str) {
+        sqlx::query(
+            "UPDATE transactions
+             SET event_type = $2::event_type, event_type_confidence = 0.75, event_type_reason = NULL
+             WHERE upstream_event = $1"
+        )
+        .bind(upstream_event)
+        .bind(event_type)
+        .execute(&self.pool)
+        .await
+        .unwrap();
+    }
+
+    /// GETs `path` from the API, whatever the response
+    pub async fn get_response(&self, path: &str) -> reqwest::Response {
+        self.http.get(format!("{}{}", self.api_url, path)).send().await.unwrap()
+    }
+
+    /// GETs `path` from the API, asserting it succeeds, and returns the JSON body
+    pub async fn get(&self, path: &str) -> Value {
+        let response = self.get_response(path).await;
+        assert!(response.status().is_success(), "GET {} returned {}", path, response.status());
+        response.json().await.unwrap()
+    }
+
+    pub async fn count(&self, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+    }
+
+    /// Drops the test's schema; a failed test leaves it behind for inspection
+    pub async fn cleanup(self) {
+        self.pool.close().await;
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", self.schema))
+            .execute(&self.admin)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,632 @@
+//! Drives ingestion against the mock Pure API into a throwaway schema, then
+//! checks the stored rows and what the API serves from them.
+//!
+//! Needs `TEST_DATABASE_URL` pointing at a Postgres database the tests may
+//! create schemas in; without it every test is skipped.
+
+mod common;
+
+use chrono::Utc;
+use common::TestContext;
+use ingestion::mock_pure_api::Faults;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+fn find<'a>(items: &'a Value, key: &str, value: &str) -> &'a Value {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item[key] == value)
+        .unwrap_or_else(|| panic!("no item with {} = {} in {}", key, value, items))
+}
+
+#[tokio::test]
+async fn test_product_sync_stores_catalog() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+
+    assert_eq!(ctx.count("products").await, 4);
+    assert_eq!(ctx.count("market_snapshots").await, 4);
+
+    let (status, attempted, inserted): (String, i32, i64) = sqlx::query_as(
+        "SELECT status, products_attempted, rows_inserted FROM sync_runs WHERE kind = 'products'"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!((status.as_str(), attempted, inserted), ("succeeded", 3, 4));
+
+    // A second sync with an unchanged catalog rewrites nothing
+    ctx.sync_products().await.unwrap();
+    let unchanged: i64 = sqlx::query_scalar(
+        "SELECT rows_unchanged FROM sync_runs WHERE kind = 'products' ORDER BY id DESC LIMIT 1"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!(unchanged, 4);
+
+    let stats = ctx.get("/products/stats").await;
+    let products = &stats["products"];
+    assert_eq!(products.as_array().unwrap().len(), 3);
+    let maple = find(products, "pure_product_id", "silver-maple-1oz");
+    assert_eq!(maple["material"], "silver");
+    assert_eq!(maple["transaction_count"], 0);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_transaction_sync_serves_activity() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    assert_eq!(ctx.count("transactions").await, 6);
+    assert_eq!(ctx.count("variant_sync_state").await, 4);
+
+    let transactions = ctx.get("/transactions").await;
+    let transactions = transactions["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 6);
+    // Newest first
+    assert_eq!(transactions[0]["pure_product_id"], "silver-maple-1oz");
+    assert_eq!(transactions[0]["event_time"], "2025-01-06T20:45:10Z");
+    assert_eq!(transactions[0]["price"], 3450);
+
+    let product = ctx.get("/product/gold-eagle-1oz").await;
+    assert_eq!(product["variants"].as_array().unwrap().len(), 2);
+    let gold_trades = &product["transactions"];
+    assert_eq!(gold_trades.as_array().unwrap().len(), 4);
+
+    // The raw upstream event is kept, but one that only sounds like a side
+    // doesn't decide it
+    let sold = find(gold_trades, "upstream_event", "Sold");
+    assert_eq!(sold["event_type_source"], "premium");
+    let purchased = find(gold_trades, "upstream_event", "Purchased");
+    assert_eq!(purchased["event_type_source"], "premium");
+    assert_eq!(purchased["variant_label"], "Tube of 20");
+
+    // Nothing was captured before the fixtures' trades to classify them against
+    let (unknown, reason): (i64, Option<String>) = sqlx::query_as(
+        "SELECT COUNT(*), MIN(event_type_reason) FROM transactions WHERE event_type = 'unknown'"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!((unknown, reason.as_deref()), (6, Some("no_snapshot_before_trade")));
+
+    ctx.set_side("Sold", "sell").await;
+    ctx.set_side("Purchased", "buy").await;
+    let stats = ctx.get("/products/stats").await;
+    let gold = find(&stats["products"], "pure_product_id", "gold-eagle-1oz");
+    assert_eq!(gold["transaction_count"], 4);
+    assert_eq!(gold["buy_count"], 1);
+    assert_eq!(gold["sell_count"], 1);
+    assert_eq!(gold["total_volume"], 272500 + 2 * 265800 + 269000 + 5310000);
+    assert_eq!(gold["total_sell_quantity"], 2);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_resync_only_writes_new_activity() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    // A trade after the product sync's market snapshot, near the lowest listing
+    let created_at = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f+00").to_string();
+    ctx.mock.update_fixtures(|fixtures| {
+        fixtures
+            .activity
+            .get_mut("silver-maple-1oz")
+            .unwrap()
+            .get_mut("silver-maple-1oz-single")
+            .unwrap()
+            .push(json!({
+                "event": "sale",
+                "createdAt": created_at,
+                "price": 3500,
+                "quantity": 5,
+                "spotPremium": 17.0,
+                "spotPremiumDollar": 520
+            }));
+    });
+    ctx.make_all_variants_due().await;
+    ctx.sync_transactions().await.unwrap();
+
+    let (inserted, updated): (i64, i64) = sqlx::query_as(
+        "SELECT rows_inserted, rows_updated FROM sync_runs WHERE kind = 'transactions' ORDER BY id DESC LIMIT 1"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!((inserted, updated), (1, 0));
+    assert_eq!(ctx.count("transactions").await, 7);
+
+    let product = ctx.get("/product/silver-maple-1oz").await;
+    let latest = &product["transactions"][0];
+    assert_eq!(latest["price"], 3500);
+    assert_eq!(latest["event_type"], "buy");
+    assert_eq!(latest["event_type_source"], "premium");
+    assert_eq!(latest["event_type_confidence"], 0.75);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_failing_product_is_recorded_and_others_still_sync() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.mock.set_faults(Faults {
+        server_errors: u32::MAX,
+        server_error_status: Some(502),
+        product_id: Some("gold-eagle-1oz".to_string()),
+        ..Default::default()
+    });
+    ctx.sync_transactions().await.unwrap();
+
+    let (status, attempted, failed): (String, i32, i32) = sqlx::query_as(
+        "SELECT status, products_attempted, products_failed FROM sync_runs WHERE kind = 'transactions'"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!((status.as_str(), attempted, failed), ("partial", 4, 2));
+
+    let failures: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT pure_product_id, error_kind FROM sync_run_failures ORDER BY pure_variant_id"
+    )
+    .fetch_all(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!(failures, vec![
+        ("gold-eagle-1oz".to_string(), Some("server".to_string())),
+        ("gold-eagle-1oz".to_string(), Some("server".to_string())),
+    ]);
+
+    let transactions = ctx.get("/transactions").await;
+    let transactions = transactions["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert!(transactions.iter().all(|t| t["pure_product_id"] != "gold-eagle-1oz"));
+
+    let runs = ctx.get("/sync/runs?kind=transactions").await;
+    assert_eq!(runs["runs"][0]["failures"].as_array().unwrap().len(), 2);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_open_circuit_stops_new_variants_and_keeps_synced_ones() {
+    let Some(mut ctx) = TestContext::new().await else { return };
+    ctx.config.activity_fetch_concurrency = 1;
+
+    ctx.sync_products().await.unwrap();
+    ctx.mock.set_faults(Faults {
+        server_errors: u32::MAX,
+        server_error_status: Some(502),
+        path: Some("/products/get-product-activity/v1".to_string()),
+        ..Default::default()
+    });
+    assert!(ctx.sync_transactions().await.is_err());
+
+    // The first variant used up its retries, the second opened the breaker on
+    // its third attempt and the other two never sent a request
+    assert_eq!(ctx.mock.request_count("/products/get-product-activity/v1"), 5);
+
+    let (status, attempted, failed, summary): (String, i32, i32, String) = sqlx::query_as(
+        "SELECT status, products_attempted, products_failed, error_summary FROM sync_runs WHERE kind = 'transactions'"
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .unwrap();
+    assert_eq!((status.as_str(), attempted, failed), ("partial", 1, 1));
+    assert!(summary.starts_with("Transaction sync aborted with 0 of 4 variants synced"), "{}", summary);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_catalog_edits_are_versioned() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    assert_eq!(ctx.count("product_history").await, 4);
+
+    // Pure relabels a variant and changes the product's SKU
+    ctx.mock.update_fixtures(|fixtures| {
+        let gold = &mut fixtures.product_options["data"][0];
+        gold["variants"][0]["label"] = json!("1 oz Coin");
+
+        let gold = fixtures.products.iter_mut().find(|p| p["id"] == "gold-eagle-1oz").unwrap();
+        gold["sku"] = json!("GE-1OZ-B");
+        gold["variants"][0]["title"] = json!("1 oz Coin");
+    });
+    ctx.sync_products().await.unwrap();
+
+    // Both gold variants changed SKU; only the single coin was relabeled
+    assert_eq!(ctx.count("product_history").await, 6);
+
+    let history = ctx.get("/product/gold-eagle-1oz/history?variant_id=gold-eagle-1oz-single").await;
+    let revisions = history["revisions"].as_array().unwrap();
+    assert_eq!(revisions.len(), 2);
+
+    let current = &revisions[0];
+    assert!(current["valid_to"].is_null());
+    assert_eq!(current["variant_label"], "1 oz Coin");
+    assert_eq!(current["changes"], json!([
+        { "field": "sku", "old": "GE-1OZ", "new": "GE-1OZ-B" },
+        { "field": "variant_label", "old": "Single Coin", "new": "1 oz Coin" },
+    ]));
+
+    let first = &revisions[1];
+    assert_eq!(first["valid_to"], current["valid_from"]);
+    assert_eq!(first["changes"], json!([]));
+
+    let history = ctx.get("/product/gold-eagle-1oz/history").await;
+    assert_eq!(history["revisions"].as_array().unwrap().len(), 4);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_delisted_variants_stop_being_polled() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    let platinum_polls = || {
+        ctx.mock
+            .requests()
+            .iter()
+            .filter(|r| r.query.as_deref().is_some_and(|q| q.contains("platinum-bar-1oz-single")))
+            .count()
+    };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+    assert_eq!(platinum_polls(), 1);
+
+    // Pure stops listing the platinum bar
+    let mut platinum = Value::Null;
+    ctx.mock.update_fixtures(|fixtures| {
+        platinum = fixtures.product_options["data"].as_array_mut().unwrap().remove(2);
+    });
+    ctx.sync_products().await.unwrap();
+
+    let delisted: Vec<String> = sqlx::query_scalar("SELECT pure_variant_id FROM products WHERE delisted_at IS NOT NULL")
+        .fetch_all(&ctx.pool)
+        .await
+        .unwrap();
+    assert_eq!(delisted, vec!["platinum-bar-1oz-single".to_string()]);
+
+    let active = ctx.get("/products/stats?status=active").await;
+    assert_eq!(active["products"].as_array().unwrap().len(), 2);
+    let delisted = ctx.get("/products/stats?status=delisted").await;
+    let delisted = &delisted["products"];
+    assert_eq!(delisted.as_array().unwrap().len(), 1);
+    assert_eq!(delisted[0]["pure_product_id"], "platinum-bar-1oz");
+    assert_eq!(delisted[0]["delisted_variant_count"], 1);
+    assert!(delisted[0]["delisted_at"].is_string());
+
+    // Still polled within the grace period, to catch its last trades
+    ctx.make_all_variants_due().await;
+    ctx.sync_transactions().await.unwrap();
+    assert_eq!(platinum_polls(), 2);
+
+    // And not at all after it
+    sqlx::query("UPDATE products SET delisted_at = NOW() - INTERVAL '2 days' WHERE delisted_at IS NOT NULL")
+        .execute(&ctx.pool)
+        .await
+        .unwrap();
+    ctx.make_all_variants_due().await;
+    ctx.sync_transactions().await.unwrap();
+    assert_eq!(platinum_polls(), 2);
+
+    // Listing it again clears the mark
+    ctx.mock.update_fixtures(|fixtures| {
+        fixtures.product_options["data"].as_array_mut().unwrap().push(platinum);
+    });
+    ctx.sync_products().await.unwrap();
+
+    let product = ctx.get("/product/platinum-bar-1oz").await;
+    assert!(product["variants"][0]["delisted_at"].is_null());
+    let active = ctx.get("/products/stats?status=active").await;
+    assert_eq!(active["products"].as_array().unwrap().len(), 3);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_transactions_are_paginated_and_filtered() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    // Walking the pages yields every transaction once, newest first
+    let mut seen = Vec::new();
+    let mut path = "/transactions?limit=4&include_total=true".to_string();
+    loop {
+        let page = ctx.get(&path).await;
+        assert_eq!(page["total_count"], 6);
+        seen.extend(page["transactions"].as_array().unwrap().iter().cloned());
+        match page["next_cursor"].as_str() {
+            Some(cursor) => path = format!("/transactions?limit=4&include_total=true&cursor={}", cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), 6);
+    let mut ids: Vec<i64> = seen.iter().map(|t| t["id"].as_i64().unwrap()).collect();
+    ids.dedup();
+    assert_eq!(ids.len(), 6);
+    assert!(seen.windows(2).all(|w| w[0]["event_time"].as_str() >= w[1]["event_time"].as_str()));
+
+    let page = ctx.get("/transactions").await;
+    assert!(page["next_cursor"].is_null());
+    assert!(page["total_count"].is_null());
+
+    let gold = ctx.get("/transactions?material=gold&min_quantity=2").await;
+    let gold = gold["transactions"].as_array().unwrap();
+    assert_eq!(gold.len(), 1);
+    assert_eq!(gold[0]["upstream_event"], "Sold");
+
+    ctx.set_side("Purchased", "buy").await;
+    let tube = ctx.get("/transactions?variant_id=gold-eagle-1oz-tube&event_type=buy").await;
+    assert_eq!(tube["transactions"].as_array().unwrap().len(), 1);
+
+    let cheap = ctx.get("/transactions?product_id=silver-maple-1oz&max_price=3300").await;
+    let cheap = cheap["transactions"].as_array().unwrap();
+    assert_eq!(cheap.len(), 1);
+    assert_eq!(cheap[0]["price"], 3290);
+
+    let recent = ctx.get("/transactions?from=2025-01-06T20:45:10Z&include_total=true").await;
+    assert_eq!(recent["total_count"], 1);
+
+    let response = ctx.get_response("/transactions?cursor=nonsense").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_errors_are_json_with_a_request_id() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+
+    let response = ctx.get_response("/product/no-such-coin").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "not_found");
+    assert_eq!(body["error"]["request_id"], request_id.as_str());
+
+    let response = ctx.get_response("/product/no-such-coin/market-history").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    for path in [
+        "/transactions?material=unobtainium",
+        "/transactions?from=yesterday",
+        "/products/stats?min_confidence=2",
+        "/sync/runs?kind=bogus",
+    ] {
+        let response = ctx.get_response(path).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "GET {}", path);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "bad_request");
+        assert!(body["error"]["request_id"].is_string());
+    }
+
+    // A client's own request ID is kept
+    let response = reqwest::Client::new()
+        .get(format!("{}/no-such-endpoint", ctx.api_url()))
+        .header("x-request-id", "trace-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["request_id"], "trace-123");
+
+    // With the database gone, requests fail instead of looking empty
+    ctx.pool.close().await;
+    let response = ctx.get_response("/transactions").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "database_unavailable");
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_candles_bucket_trades_in_the_requested_time_zone() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    let daily = ctx.get("/product/gold-eagle-1oz/candles?variant_id=gold-eagle-1oz-single").await;
+    assert_eq!(daily["tz"], "UTC");
+    assert_eq!(daily["candles"], json!([
+        {
+            "bucket_start": "2025-01-05T00:00:00Z",
+            "open_price": 269000, "high_price": 269000, "low_price": 269000, "close_price": 269000,
+            "open_premium": 3.4, "high_premium": 3.4, "low_premium": 3.4, "close_premium": 3.4,
+            "volume": 269000, "quantity": 1, "trade_count": 1,
+        },
+        {
+            "bucket_start": "2025-01-06T00:00:00Z",
+            "open_price": 265800, "high_price": 272500, "low_price": 265800, "close_price": 272500,
+            "open_premium": 2.2, "high_premium": 4.4, "low_premium": 2.2, "close_premium": 4.4,
+            "volume": 2 * 265800 + 272500, "quantity": 3, "trade_count": 2,
+        },
+    ]));
+
+    // Tokyo's days start at 15:00 UTC, so the 5th's evening trade opens the 6th
+    let tokyo = ctx.get("/product/gold-eagle-1oz/candles?variant_id=gold-eagle-1oz-single&tz=Asia/Tokyo").await;
+    let candles = tokyo["candles"].as_array().unwrap();
+    assert_eq!(candles.len(), 2);
+    assert_eq!(candles[0]["bucket_start"], "2025-01-05T15:00:00Z");
+    assert_eq!((candles[0]["open_price"].as_i64(), candles[0]["close_price"].as_i64()), (Some(269000), Some(265800)));
+    assert_eq!(candles[1]["trade_count"], 1);
+
+    // Weeks start on Monday the 6th; both variants count without a filter
+    let weekly = ctx.get("/product/gold-eagle-1oz/candles?interval=1w").await;
+    let candles = weekly["candles"].as_array().unwrap();
+    assert_eq!(candles.len(), 2);
+    assert_eq!(candles[0]["bucket_start"], "2024-12-30T00:00:00Z");
+    assert_eq!(candles[0]["trade_count"], 2);
+    assert_eq!(candles[1]["trade_count"], 2);
+
+    let ranged = ctx.get("/product/gold-eagle-1oz/candles?interval=1h&from=2025-01-06T00:00:00Z&to=2025-01-06T12:00:00Z").await;
+    assert_eq!(ranged["candles"].as_array().unwrap().len(), 1);
+    assert_eq!(ranged["candles"][0]["bucket_start"], "2025-01-06T09:00:00Z");
+
+    for path in ["/product/gold-eagle-1oz/candles?tz=Mars/Olympus_Mons", "/product/gold-eagle-1oz/candles?interval=5m"] {
+        assert_eq!(ctx.get_response(path).await.status(), StatusCode::BAD_REQUEST, "GET {}", path);
+    }
+    assert_eq!(ctx.get_response("/product/no-such-coin/candles").await.status(), StatusCode::NOT_FOUND);
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_analytics_weight_prices_and_premiums_by_quantity() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    let close = |value: &Value, expected: f64| {
+        let value = value.as_f64().unwrap_or_else(|| panic!("{} is not a number", value));
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    };
+
+    ctx.set_side("Sold", "sell").await;
+    ctx.set_side("Purchased", "buy").await;
+
+    let analytics = ctx.get("/product/gold-eagle-1oz/analytics").await;
+    let variants = analytics["variants"].as_array().unwrap();
+    assert_eq!(variants.len(), 2);
+
+    let single = &variants[0];
+    assert_eq!(single["pure_variant_id"], "gold-eagle-1oz-single");
+    let all = &single["all"];
+    assert_eq!((all["trade_count"].as_i64(), all["quantity"].as_i64()), (Some(3), Some(4)));
+    assert_eq!(all["volume"], 272500 + 2 * 265800 + 269000);
+    close(&all["vwap"], 1073100.0 / 4.0);
+    close(&all["vw_premium_percentage"], (4.4 + 2.0 * 2.2 + 3.4) / 4.0);
+    close(&all["vw_premium_dollar"], (11500.0 + 2.0 * 5700.0 + 8800.0) / 4.0);
+    close(&all["median_premium_percentage"], 3.4);
+    close(&all["p10_premium_percentage"], 2.44);
+    close(&all["p90_premium_percentage"], 4.2);
+
+    // Only the "Sold" trade was given a side
+    assert!(single["buy"].is_null());
+    assert_eq!(single["sell"]["trade_count"], 1);
+    close(&single["sell"]["vwap"], 265800.0);
+
+    let tube = &variants[1];
+    assert_eq!(tube["buy"]["trade_count"], 1);
+    assert!(tube["sell"].is_null());
+
+    let filtered = ctx.get("/product/gold-eagle-1oz/analytics?variant_id=gold-eagle-1oz-single&from=2025-01-06T00:00:00Z").await;
+    let variants = filtered["variants"].as_array().unwrap();
+    assert_eq!(variants.len(), 1);
+    assert_eq!(variants[0]["all"]["trade_count"], 2);
+
+    // A variant with no trades in the window is still listed
+    let quiet = ctx.get("/product/platinum-bar-1oz/analytics").await;
+    let all = &quiet["variants"][0]["all"];
+    assert_eq!((all["trade_count"].as_i64(), all["quantity"].as_i64()), (Some(0), Some(0)));
+    assert!(all["vwap"].is_null());
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_product_stats_cover_a_window_and_compare_it_with_the_previous_one() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    // The fixtures' trades are all from early January 2025
+    let recent = ctx.get("/products/stats?window=7d").await;
+    assert!(recent["from"].is_string());
+    let products = recent["products"].as_array().unwrap();
+    assert_eq!(products.len(), 3);
+    assert!(products.iter().all(|p| p["transaction_count"] == 0));
+
+    ctx.set_side("Sold", "sell").await;
+
+    let stats = ctx.get("/products/stats?from=2025-01-05T00:00:00Z&to=2025-01-07T00:00:00Z&compare=true").await;
+    assert_eq!(stats["previous_from"], "2025-01-03T00:00:00Z");
+    assert_eq!(stats["previous_to"], "2025-01-05T00:00:00Z");
+
+    // Three single coin trades against one tube the two days before
+    let gold = find(&stats["products"], "pure_product_id", "gold-eagle-1oz");
+    assert_eq!(gold["transaction_count"], 3);
+    assert_eq!(gold["previous"]["transaction_count"], 1);
+    assert_eq!(gold["percent_change"]["transaction_count"], 200.0);
+    assert_eq!(gold["percent_change"]["total_volume"], (1073100.0 - 5310000.0) / 5310000.0 * 100.0);
+    assert_eq!(gold["sell_count"], 1);
+    assert!(gold["percent_change"]["sell_count"].is_null());
+
+    let maple = find(&stats["products"], "pure_product_id", "silver-maple-1oz");
+    assert_eq!(maple["percent_change"]["transaction_count"], 0.0);
+
+    // Without compare there is nothing to compare against
+    let stats = ctx.get("/products/stats?from=2025-01-05T00:00:00Z").await;
+    assert!(stats["to"].is_null());
+    assert!(find(&stats["products"], "pure_product_id", "gold-eagle-1oz")["previous"].is_null());
+
+    for path in [
+        "/products/stats?window=all&compare=true",
+        "/products/stats?window=7d&from=2025-01-01T00:00:00Z",
+        "/products/stats?window=1y",
+    ] {
+        assert_eq!(ctx.get_response(path).await.status(), StatusCode::BAD_REQUEST, "GET {}", path);
+    }
+
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn test_min_confidence_keeps_trades_in_totals_but_off_either_side() {
+    let Some(ctx) = TestContext::new().await else { return };
+
+    ctx.sync_products().await.unwrap();
+    ctx.sync_transactions().await.unwrap();
+
+    // Both sides are classified with 0.75 confidence
+    ctx.set_side("Sold", "sell").await;
+    ctx.set_side("Purchased", "buy").await;
+
+    for (min_confidence, sided) in [("0.5", true), ("0.9", false)] {
+        let stats = ctx.get(&format!("/products/stats?min_confidence={}", min_confidence)).await;
+        let gold = find(&stats["products"], "pure_product_id", "gold-eagle-1oz");
+        let analytics = ctx.get(&format!("/product/gold-eagle-1oz/analytics?min_confidence={}", min_confidence)).await;
+        let variants = analytics["variants"].as_array().unwrap();
+
+        // Every trade counts toward the totals on both endpoints
+        let trade_count: i64 = variants.iter().map(|v| v["all"]["trade_count"].as_i64().unwrap()).sum();
+        let volume: i64 = variants.iter().map(|v| v["all"]["volume"].as_i64().unwrap()).sum();
+        assert_eq!(gold["transaction_count"], trade_count, "min_confidence={}", min_confidence);
+        assert_eq!(gold["total_volume"], volume, "min_confidence={}", min_confidence);
+        assert_eq!(trade_count, 4);
+
+        // But only confidently classified ones count toward a side
+        let buys: i64 = variants.iter().filter_map(|v| v["buy"]["trade_count"].as_i64()).sum();
+        let sells: i64 = variants.iter().filter_map(|v| v["sell"]["trade_count"].as_i64()).sum();
+        assert_eq!(gold["buy_count"], buys, "min_confidence={}", min_confidence);
+        assert_eq!(gold["sell_count"], sells, "min_confidence={}", min_confidence);
+        assert_eq!(buys + sells > 0, sided, "min_confidence={}", min_confidence);
+    }
+
+    ctx.cleanup().await;
+}
@@ -1,3 +1,8 @@
 pub mod models;
+pub mod money;
 
-pub use models::{Product, Transaction, NewProduct, NewTransaction};
+pub use models::{
+    EventType, MarketSnapshot, Material, NewProduct, NewTransaction, Product, SyncRunKind,
+    SyncRunStatus, Transaction, UpstreamEventKind,
+};
+pub use money::Money;
@@ -2,18 +2,168 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::money::Money;
+
+/// Which side of the market initiated a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "event_type", rename_all = "lowercase")]
+pub enum EventType {
+    Buy,
+    Sell,
+    Unknown,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buy => "buy",
+            Self::Sell => "sell",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Precious metal a product is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "material", rename_all = "lowercase")]
+pub enum Material {
+    Gold,
+    Silver,
+    Platinum,
+    Palladium,
+    Copper,
+    /// Any material we don't recognise
+    Other,
+}
+
+impl Material {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gold => "gold",
+            Self::Silver => "silver",
+            Self::Platinum => "platinum",
+            Self::Palladium => "palladium",
+            Self::Copper => "copper",
+            Self::Other => "other",
+        }
+    }
+
+    /// Maps the raw upstream material string to a material, case-insensitively
+    pub fn from_raw(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "gold" => Self::Gold,
+            "silver" => Self::Silver,
+            "platinum" => Self::Platinum,
+            "palladium" => Self::Palladium,
+            "copper" => Self::Copper,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Kind of activity reported by the Pure activity endpoint's `event` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamEventKind {
+    /// A completed trade that does not say which side initiated it
+    Sale,
+    /// Any value whose meaning we don't know
+    Other,
+}
+
+impl UpstreamEventKind {
+    /// Maps the raw upstream `event` string to a kind, case-insensitively
+    pub fn from_raw(raw: &str) -> Self {
+        // Pure doesn't document the values of `event`; `ActivityEvent` in
+        // ingestion/src/pure_api.rs keeps it as a free string. "sale" is the value
+        // in the activity fixtures (ingestion/fixtures/pure_api/product_activity.json)
+        // and names no side. Values that sound like a side, such as "Sold", are
+        // ambiguous on a marketplace feed ("Sold" may mean a buyer lifted the
+        // listing), so they stay `Other` and the premium heuristic decides until
+        // their meaning is documented.
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "sale" => Self::Sale,
+            _ => Self::Other,
+        }
+    }
+
+    /// The trade side implied by this kind, if it implies one; no kind
+    /// currently does
+    pub fn side(&self) -> Option<EventType> {
+        match self {
+            Self::Sale | Self::Other => None,
+        }
+    }
+}
+
+/// What a row in `sync_runs` was a run of, stored as TEXT in its `kind` column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncRunKind {
+    /// Run of `sync_products`
+    Products,
+    /// Run of `sync_transactions`
+    Transactions,
+    /// Run of the `backfill_event_types` binary
+    BackfillEventTypes,
+    /// Run of the `backfill_image_urls` binary
+    BackfillImageUrls,
+}
+
+impl SyncRunKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Products => "products",
+            Self::Transactions => "transactions",
+            Self::BackfillEventTypes => "backfill_event_types",
+            Self::BackfillImageUrls => "backfill_image_urls",
+        }
+    }
+}
+
+/// State of a row in `sync_runs`, stored as TEXT in its `status` column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncRunStatus {
+    Running,
+    Succeeded,
+    /// Finished, but some products failed and were skipped
+    Partial,
+    Failed,
+}
+
+impl SyncRunStatus {
+    /// Statuses of runs that got to the end, whether or not every product succeeded
+    pub const COMPLETED: [Self; 2] = [Self::Succeeded, Self::Partial];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Partial => "partial",
+            Self::Failed => "failed",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Transaction {
     pub id: i64,
     pub product_id: i64,
     pub pure_product_id: String,
     pub pure_variant_id: String,
-    pub price: f64,
+    pub price: Money,
     pub quantity: i32,
     pub spot_premium_percentage: f64,
-    pub spot_premium_dollar: f64,
+    pub spot_premium_dollar: Money,
     pub event_time: DateTime<Utc>,
-    pub event_type: Option<String>,
+    pub event_type: Option<EventType>,
+    pub event_type_confidence: Option<f64>,
+    pub event_type_reason: Option<String>,
+    pub event_type_source: Option<String>,
+    pub upstream_event: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -25,16 +175,29 @@ pub struct Product {
     pub pure_variant_id: String,
     pub name: String,
     pub sku: String,
-    pub material: String,
+    pub material: Material,
     pub variant_label: String,
     pub image_url: Option<String>,
     pub highest_offer_spot_premium: Option<f64>,
     pub lowest_listing_spot_premium: Option<f64>,
     pub market_data_updated_at: Option<DateTime<Utc>>,
+    /// When the variant stopped being listed on Pure; `None` while it is listed
+    pub delisted_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MarketSnapshot {
+    pub id: i64,
+    pub product_id: i64,
+    pub pure_product_id: String,
+    pub pure_variant_id: String,
+    pub highest_offer_spot_premium: Option<f64>,
+    pub lowest_listing_spot_premium: Option<f64>,
+    pub captured_at: DateTime<Utc>,
+}
+
 // Struct for inserting new products (without auto-generated fields)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewProduct {
@@ -42,7 +205,7 @@ pub struct NewProduct {
     pub pure_variant_id: String,
     pub name: String,
     pub sku: String,
-    pub material: String,
+    pub material: Material,
     pub variant_label: String,
     pub image_url: Option<String>,
     pub highest_offer_spot_premium: Option<f64>,
@@ -56,10 +219,14 @@ pub struct NewTransaction {
     pub product_id: i64,
     pub pure_product_id: String,
     pub pure_variant_id: String,
-    pub price: f64,
+    pub price: Money,
     pub quantity: i32,
     pub spot_premium_percentage: f64,
-    pub spot_premium_dollar: f64,
+    pub spot_premium_dollar: Money,
     pub event_time: DateTime<Utc>,
-    pub event_type: Option<String>,
+    pub event_type: Option<EventType>,
+    pub event_type_confidence: Option<f64>,
+    pub event_type_reason: Option<String>,
+    pub event_type_source: Option<String>,
+    pub upstream_event: Option<String>,
 }
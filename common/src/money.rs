@@ -0,0 +1,102 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Mul};
+
+/// An exact amount of US dollars, held as integer cents
+///
+/// Stored in `BIGINT` columns and serialized as a JSON integer of cents, so
+/// $2,345.67 is `234567`. Upstream amounts with fractional cents are rounded
+/// to the nearest cent when deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, sqlx::Type)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Self(cents)
+    }
+
+    /// Rounds a floating point amount of cents to the nearest cent
+    pub fn from_cents_f64(cents: f64) -> Self {
+        Self(cents.round() as i64)
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Self::from_cents_f64)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+/// Total for a quantity of units at this price
+impl Mul<i32> for Money {
+    type Output = Money;
+
+    fn mul(self, quantity: i32) -> Money {
+        Money(self.0 * i64::from(quantity))
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+        write!(f, "{}${}.{:02}", sign, cents / 100, cents % 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserializes_and_rounds_cents() {
+        let money: Money = serde_json::from_str("234567").unwrap();
+        assert_eq!(money, Money::from_cents(234567));
+
+        let money: Money = serde_json::from_str("1999.6").unwrap();
+        assert_eq!(money, Money::from_cents(2000));
+    }
+
+    #[test]
+    fn test_serializes_as_integer_cents() {
+        assert_eq!(serde_json::to_string(&Money::from_cents(234567)).unwrap(), "234567");
+    }
+
+    #[test]
+    fn test_totals_are_exact() {
+        let total: Money = [Money::from_cents(10), Money::from_cents(20)]
+            .into_iter()
+            .map(|price| price * 3)
+            .sum();
+        assert_eq!(total, Money::from_cents(90));
+    }
+
+    #[test]
+    fn test_display_in_dollars() {
+        assert_eq!(Money::from_cents(234567).to_string(), "$2345.67");
+        assert_eq!(Money::from_cents(-5).to_string(), "-$0.05");
+    }
+}
@@ -1,10 +1,42 @@
 use anyhow::Result;
-use common::Product;
+use chrono::{DateTime, Utc};
+use common::{MarketSnapshot, SyncRunKind};
 use ingestion::config::Config;
-use ingestion::event_type;
-use ingestion::pure_api::PureApiClient;
-use sqlx::postgres::PgPoolOptions;
-use tracing::{info, error};
+use ingestion::event_type::TradeClassifier;
+use ingestion::sync_runs::{SyncRun, UpsertCounts};
+use sqlx::{FromRow, PgPool, postgres::PgPoolOptions};
+use tracing::info;
+
+/// A transaction joined with the latest market snapshot captured before it
+#[derive(Debug, FromRow)]
+struct TransactionWithSnapshot {
+    id: i64,
+    pure_product_id: String,
+    pure_variant_id: String,
+    spot_premium: f64,
+    upstream_event: Option<String>,
+    previous_premium: Option<f64>,
+    event_time: DateTime<Utc>,
+    snapshot_id: Option<i64>,
+    snapshot_product_id: Option<i64>,
+    highest_offer_spot_premium: Option<f64>,
+    lowest_listing_spot_premium: Option<f64>,
+    captured_at: Option<DateTime<Utc>>,
+}
+
+impl TransactionWithSnapshot {
+    fn snapshot(&self) -> Option<MarketSnapshot> {
+        Some(MarketSnapshot {
+            id: self.snapshot_id?,
+            product_id: self.snapshot_product_id?,
+            pure_product_id: self.pure_product_id.clone(),
+            pure_variant_id: self.pure_variant_id.clone(),
+            highest_offer_spot_premium: self.highest_offer_spot_premium,
+            lowest_listing_spot_premium: self.lowest_listing_spot_premium,
+            captured_at: self.captured_at?,
+        })
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -33,130 +65,138 @@ async fn main() -> Result<()> {
         .await?;
     info!("Database migrations completed");
 
-    // Initialize Pure API client
-    let pure_client = PureApiClient::new(&config)?;
-
-    // Step 1: Fetch fresh market data from API and update products table
-    info!("Step 1: Fetching fresh market data from Pure API");
-    match pure_client.build_new_products().await {
-        Ok(products) => {
-            info!("Fetched {} products with market data", products.len());
-
-            // Update products table with market data
-            info!("Updating products table with market data");
-            for (index, product) in products.iter().enumerate() {
-                if (index + 1) % 100 == 0 {
-                    info!("Updated {}/{} products", index + 1, products.len());
-                }
-
-                sqlx::query(
-                    r#"
-                    UPDATE products
-                    SET highest_offer_spot_premium = $1,
-                        lowest_listing_spot_premium = $2,
-                        market_data_updated_at = $3,
-                        updated_at = NOW()
-                    WHERE pure_product_id = $4 AND pure_variant_id = $5
-                    "#
-                )
-                .bind(product.highest_offer_spot_premium)
-                .bind(product.lowest_listing_spot_premium)
-                .bind(product.market_data_updated_at)
-                .bind(&product.pure_product_id)
-                .bind(&product.pure_variant_id)
-                .execute(&pool)
-                .await?;
-            }
-            info!("Successfully updated {} products with market data", products.len());
-        }
+    let classifier = TradeClassifier::from_config(&config)?;
+
+    let mut run = SyncRun::start(&pool, SyncRunKind::BackfillEventTypes).await?;
+    match backfill(&pool, &config, &classifier, &mut run).await {
+        Ok(()) => run.finish(&pool).await,
         Err(e) => {
-            error!("Failed to fetch products from API: {}", e);
-            return Err(e);
+            run.fail(&pool, &e).await?;
+            Err(e)
         }
     }
+}
 
-    // Step 2: Fetch all products from database (now with market data)
-    info!("Step 2: Fetching products from database");
-    let products = sqlx::query_as::<_, Product>("SELECT * FROM products")
-        .fetch_all(&pool)
-        .await?;
-    info!("Fetched {} products from database", products.len());
-
-    // Create a map of (pure_product_id, pure_variant_id) -> Product for quick lookup
-    let mut product_map = std::collections::HashMap::new();
-    for product in products {
-        product_map.insert(
-            (product.pure_product_id.clone(), product.pure_variant_id.clone()),
-            product
-        );
-    }
-
-    // Step 3: Fetch all transactions
-    info!("Step 3: Fetching all transactions from database");
+/// Reclassifies every transaction, recording the rows written against `run`
+async fn backfill(
+    pool: &PgPool,
+    config: &Config,
+    classifier: &TradeClassifier,
+    run: &mut SyncRun,
+) -> Result<()> {
+    // Step 1: Count transactions to reclassify
+    info!("Step 1: Counting transactions in database");
     let transaction_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions")
-        .fetch_one(&pool)
+        .fetch_one(pool)
         .await?;
     info!("Found {} transactions to backfill", transaction_count);
 
+    // Step 2: Reclassify each transaction against the snapshot in effect at its event_time
+    info!(
+        "Step 2: Classifying transactions against market snapshots (classifier {}, max age {}s)",
+        config.side_classifier,
+        config.snapshot_max_age.as_secs()
+    );
+
     // Process transactions in batches
     let batch_size = 1000;
     let mut offset = 0;
     let mut updated_count = 0;
+    let mut unknown_count = 0;
 
     loop {
-        let transactions: Vec<(i64, String, String, f64)> = sqlx::query_as(
+        let rows: Vec<TransactionWithSnapshot> = sqlx::query_as(
             r#"
-            SELECT id, pure_product_id, pure_variant_id, spot_premium_percentage::FLOAT8
-            FROM transactions
-            ORDER BY id
+            SELECT
+                t.id,
+                t.pure_product_id,
+                t.pure_variant_id,
+                t.spot_premium_percentage::FLOAT8 as spot_premium,
+                t.upstream_event,
+                prev.spot_premium_percentage::FLOAT8 as previous_premium,
+                t.event_time,
+                s.id as snapshot_id,
+                s.product_id as snapshot_product_id,
+                s.highest_offer_spot_premium,
+                s.lowest_listing_spot_premium,
+                s.captured_at
+            FROM transactions t
+            LEFT JOIN LATERAL (
+                SELECT *
+                FROM market_snapshots ms
+                WHERE ms.pure_product_id = t.pure_product_id
+                    AND ms.pure_variant_id = t.pure_variant_id
+                    AND ms.captured_at <= t.event_time
+                ORDER BY ms.captured_at DESC
+                LIMIT 1
+            ) s ON TRUE
+            LEFT JOIN LATERAL (
+                SELECT pt.spot_premium_percentage
+                FROM transactions pt
+                WHERE pt.pure_product_id = t.pure_product_id
+                    AND pt.pure_variant_id = t.pure_variant_id
+                    AND pt.event_time < t.event_time
+                ORDER BY pt.event_time DESC
+                LIMIT 1
+            ) prev ON TRUE
+            ORDER BY t.id
             LIMIT $1 OFFSET $2
             "#
         )
         .bind(batch_size as i64)
         .bind(offset)
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?;
 
-        if transactions.is_empty() {
+        if rows.is_empty() {
             break;
         }
 
-        for (transaction_id, pure_product_id, pure_variant_id, spot_premium) in transactions {
-            // Look up the product to get market data
-            if let Some(product) = product_map.get(&(pure_product_id.clone(), pure_variant_id.clone())) {
-                // Calculate event type
-                let event_type = event_type::determine_event_type(
-                    spot_premium,
-                    product.highest_offer_spot_premium,
-                    product.lowest_listing_spot_premium,
-                );
-
-                // Update transaction with event_type
-                sqlx::query(
-                    r#"
-                    UPDATE transactions
-                    SET event_type = $1, updated_at = NOW()
-                    WHERE id = $2
-                    "#
-                )
-                .bind(&event_type)
-                .bind(transaction_id)
-                .execute(&pool)
-                .await?;
-
-                updated_count += 1;
-            } else {
-                error!(
-                    "Product not found for transaction {}: product_id={}, variant_id={}",
-                    transaction_id, pure_product_id, pure_variant_id
-                );
+        let mut rows_updated = 0;
+        for row in rows {
+            let snapshot = row.snapshot();
+            let classification = classifier.classify(
+                row.upstream_event.as_deref().unwrap_or_default(),
+                row.spot_premium,
+                row.event_time,
+                snapshot.as_ref(),
+                row.previous_premium,
+            );
+
+            if classification.reason.is_some() {
+                unknown_count += 1;
             }
+
+            // Update transaction with event_type
+            sqlx::query(
+                r#"
+                UPDATE transactions
+                SET event_type = $1,
+                    event_type_confidence = $2,
+                    event_type_reason = $3,
+                    event_type_source = $4,
+                    updated_at = NOW()
+                WHERE id = $5
+                "#
+            )
+            .bind(classification.event_type)
+            .bind(classification.confidence)
+            .bind(&classification.reason)
+            .bind(&classification.source)
+            .bind(row.id)
+            .execute(pool)
+            .await?;
+
+            updated_count += 1;
+            rows_updated += 1;
         }
 
+        run.record_rows(UpsertCounts { updated: rows_updated, ..Default::default() });
         offset += batch_size;
         info!("Progress: Updated {}/{} transactions", updated_count, transaction_count);
     }
 
+    info!("{} transactions had no usable snapshot and were marked unknown", unknown_count);
     info!("Backfill completed! Updated {} transactions with event_type", updated_count);
 
     Ok(())
@@ -1,7 +1,10 @@
 use anyhow::Result;
+use common::SyncRunKind;
 use ingestion::config::Config;
+use ingestion::product_history::record_product_history;
 use ingestion::pure_api::PureApiClient;
-use sqlx::postgres::PgPoolOptions;
+use ingestion::sync_runs::{SyncRun, UpsertCounts};
+use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing::{info, error};
 
 #[tokio::main]
@@ -34,12 +37,36 @@ async fn main() -> Result<()> {
     // Initialize Pure API client
     let pure_client = PureApiClient::new(&config)?;
 
+    let mut run = SyncRun::start(&pool, SyncRunKind::BackfillImageUrls).await?;
+    match backfill(&pool, &pure_client, &mut run).await {
+        Ok(()) => run.finish(&pool).await?,
+        Err(e) => {
+            run.fail(&pool, &e).await?;
+            return Err(e);
+        }
+    }
+
+    info!("Image URL backfill completed!");
+
+    Ok(())
+}
+
+/// Refreshes image URLs from the Pure API, recording the rows written against `run`
+async fn backfill(pool: &PgPool, pure_client: &PureApiClient, run: &mut SyncRun) -> Result<()> {
     // Fetch fresh product data with images from Pure API
     info!("Fetching fresh product data with images from Pure API");
     match pure_client.build_new_products().await {
-        Ok(products) => {
+        Ok(catalog) => {
+            let products = catalog.products;
             info!("Fetched {} products with image data", products.len());
 
+            run.record_attempts(catalog.products_attempted as u64);
+            for batch in &catalog.failed_batches {
+                for product_id in &batch.product_ids {
+                    run.record_failure(pool, Some(product_id), None, Some(batch.error_kind), &batch.error).await;
+                }
+            }
+
             // Update products table with image URLs
             info!("Updating products table with image URLs");
             let mut updated_count = 0;
@@ -49,25 +76,31 @@ async fn main() -> Result<()> {
                     info!("Progress: {}/{} products processed", index + 1, products.len());
                 }
 
-                let result = sqlx::query(
+                // Versioned like any other catalog change
+                let mut tx = pool.begin().await?;
+                let updated_ids: Vec<i64> = sqlx::query_scalar(
                     r#"
                     UPDATE products
                     SET image_url = $1,
                         updated_at = NOW()
                     WHERE pure_product_id = $2 AND pure_variant_id = $3
+                    RETURNING id
                     "#
                 )
                 .bind(&product.image_url)
                 .bind(&product.pure_product_id)
                 .bind(&product.pure_variant_id)
-                .execute(&pool)
+                .fetch_all(&mut *tx)
                 .await?;
+                record_product_history(&mut tx, &updated_ids).await?;
+                tx.commit().await?;
 
-                if result.rows_affected() > 0 {
+                if !updated_ids.is_empty() {
                     updated_count += 1;
                 }
             }
 
+            run.record_rows(UpsertCounts { updated: updated_count as u64, ..Default::default() });
             info!("Successfully updated {} products with image URLs", updated_count);
             info!("Products without images: {}", products.len() - updated_count);
         }
@@ -77,7 +110,5 @@ async fn main() -> Result<()> {
         }
     }
 
-    info!("Image URL backfill completed!");
-
     Ok(())
 }
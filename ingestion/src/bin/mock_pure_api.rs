@@ -0,0 +1,51 @@
+use anyhow::Result;
+use ingestion::mock_pure_api::{Faults, Fixtures, MockPureApi, DEFAULT_FIXTURES_DIR};
+use tracing::info;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    dotenvy::dotenv().ok();
+
+    let port: u16 = env_or("MOCK_PURE_API_PORT", 4010);
+    let fixtures_dir = std::env::var("MOCK_PURE_API_FIXTURES")
+        .unwrap_or_else(|_| DEFAULT_FIXTURES_DIR.to_string());
+
+    let fixtures = Fixtures::load(&fixtures_dir)?;
+    info!(
+        "Loaded fixtures from {}: {} products, activity for {} products",
+        fixtures_dir, fixtures.products.len(), fixtures.activity.len()
+    );
+
+    // Faults to start with; they can be changed later with PUT /mock/faults
+    let faults = Faults {
+        rate_limited: env_or("MOCK_RATE_LIMITED", 0),
+        server_errors: env_or("MOCK_SERVER_ERRORS", 0),
+        server_error_status: std::env::var("MOCK_SERVER_ERROR_STATUS").ok().and_then(|v| v.parse().ok()),
+        retry_after_secs: std::env::var("MOCK_RETRY_AFTER_SECS").ok().and_then(|v| v.parse().ok()),
+        malformed: env_or("MOCK_MALFORMED", 0),
+        delay_ms: env_or("MOCK_DELAY_MS", 0),
+        path: std::env::var("MOCK_FAULT_PATH").ok(),
+        product_id: std::env::var("MOCK_FAULT_PRODUCT_ID").ok(),
+    };
+    if faults != Faults::default() {
+        info!("Injecting faults: {:?}", faults);
+    }
+
+    let mock = MockPureApi::new(fixtures);
+    mock.set_faults(faults);
+
+    let addr = format!("127.0.0.1:{}", port);
+    info!("Mock Pure API listening on http://{}", addr);
+    info!("Point ingestion at it with API_BASE_URL=http://{}", addr);
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    mock.serve(listener).await
+}
@@ -0,0 +1,307 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::{error, info};
+
+use crate::error::PureApiError;
+
+/// Stands in for the API key wherever it would have been recorded
+const REDACTED: &str = "[REDACTED]";
+
+/// Whether Pure API traffic is recorded to, or replayed from, a cassette directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Requests go to the API and nothing is saved
+    #[default]
+    Off,
+    /// Requests go to the API and every response is saved
+    Record,
+    /// Responses are served from the cassette and nothing is sent
+    Replay,
+}
+
+impl CassetteMode {
+    /// Looks up a mode by the name it is configured with
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "" | "off" => Ok(Self::Off),
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            other => Err(anyhow!("Unknown cassette mode: {}", other)),
+        }
+    }
+}
+
+/// One request and the response it got, as saved to disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub recorded_at: DateTime<Utc>,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// The body as received, decoded as lossy UTF-8 with the API key redacted;
+    /// invalid bytes and redacted values won't reproduce on replay
+    pub body: String,
+}
+
+impl RecordedResponse {
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn header_map(&self) -> HeaderMap {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?))
+            })
+            .collect()
+    }
+}
+
+/// Saves every Pure API response to a directory, one numbered file each
+#[derive(Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    api_key: String,
+    next_seq: AtomicU64,
+}
+
+impl Recorder {
+    /// Records into `dir`, numbering after any interactions already in it
+    pub fn new(dir: &Path, api_key: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create cassette directory {}", dir.display()))?;
+
+        let last_seq = interaction_files(dir)?
+            .iter()
+            .filter_map(|(seq, _)| *seq)
+            .max()
+            .unwrap_or(0);
+
+        info!("Recording Pure API responses to {}", dir.display());
+        Ok(Self { dir: dir.to_path_buf(), api_key: api_key.to_string(), next_seq: AtomicU64::new(last_seq + 1) })
+    }
+
+    /// Saves one interaction, logging rather than failing the request if it can't
+    pub fn record(&self, path: &str, query: &[(&str, &str)], status: StatusCode, headers: &HeaderMap, body: &[u8]) {
+        let interaction = Interaction {
+            recorded_at: Utc::now(),
+            request: RecordedRequest {
+                method: "GET".to_string(),
+                path: self.redact(path),
+                query: query.iter().map(|(k, v)| (k.to_string(), self.redact(v))).collect(),
+                headers: BTreeMap::from([("x-api-key".to_string(), REDACTED.to_string())]),
+            },
+            response: RecordedResponse {
+                status: status.as_u16(),
+                headers: headers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.to_string(), self.redact(value.to_str().ok()?))))
+                    .collect(),
+                body: self.redact(&String::from_utf8_lossy(body)),
+            },
+        };
+
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let file = self.dir.join(format!("{:06}-{}.json", seq, endpoint_name(path)));
+        let written = serde_json::to_vec_pretty(&interaction)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| std::fs::write(&file, json).map_err(anyhow::Error::from));
+
+        if let Err(e) = written {
+            error!("Failed to record Pure API response to {}: {}", file.display(), e);
+        }
+    }
+
+    /// Removes the API key wherever it appears
+    fn redact(&self, value: &str) -> String {
+        if self.api_key.is_empty() {
+            value.to_string()
+        } else {
+            value.replace(&self.api_key, REDACTED)
+        }
+    }
+}
+
+/// Serves recorded responses in place of the Pure API
+///
+/// Each request gets the responses recorded for the same path and query in the
+/// order they were recorded, so a failure followed by a successful retry replays
+/// the same way. Once they run out, the last one is repeated.
+#[derive(Debug)]
+pub struct Replayer {
+    responses: Mutex<HashMap<RequestKey, ReplayQueue>>,
+}
+
+type RequestKey = (String, Vec<(String, String)>);
+
+#[derive(Debug)]
+struct ReplayQueue {
+    responses: Vec<RecordedResponse>,
+    served: usize,
+}
+
+impl Replayer {
+    /// Loads every interaction in `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut responses: HashMap<RequestKey, ReplayQueue> = HashMap::new();
+        let files = interaction_files(dir)?;
+
+        for (_, file) in &files {
+            let contents = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read cassette file {}", file.display()))?;
+            let interaction: Interaction = serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse cassette file {}", file.display()))?;
+
+            responses
+                .entry((interaction.request.path, interaction.request.query))
+                .or_insert_with(|| ReplayQueue { responses: Vec::new(), served: 0 })
+                .responses
+                .push(interaction.response);
+        }
+
+        info!("Replaying {} recorded Pure API responses from {}", files.len(), dir.display());
+        Ok(Self { responses: Mutex::new(responses) })
+    }
+
+    /// The next recorded response to this request
+    pub fn next(&self, path: &str, query: &[(&str, &str)]) -> Result<RecordedResponse, PureApiError> {
+        let key = (path.to_string(), query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+        let mut responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+
+        let queue = responses.get_mut(&key).ok_or_else(|| {
+            let query = key.1.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+            PureApiError::NotRecorded(format!("GET {}?{}", path, query))
+        })?;
+
+        let response = queue.responses[queue.served.min(queue.responses.len() - 1)].clone();
+        queue.served += 1;
+        Ok(response)
+    }
+}
+
+/// Interaction files in `dir` sorted by name, with the sequence number each starts with
+fn interaction_files(dir: &Path) -> Result<Vec<(Option<u64>, PathBuf)>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read cassette directory {}", dir.display()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let seq = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split('-').next())
+                .and_then(|seq| seq.parse().ok());
+            files.push((seq, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `get-product-activity` for `/products/get-product-activity/v1`
+fn endpoint_name(path: &str) -> &str {
+    let is_version = |segment: &str| segment.strip_prefix('v').is_some_and(|n| n.parse::<u32>().is_ok());
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty() && !is_version(segment))
+        .unwrap_or("request")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_pure_api::{client_config, Faults, Fixtures, MockPureApi, DEFAULT_FIXTURES_DIR};
+    use crate::pure_api::PureApiClient;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pure-cassette-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_endpoint_name_drops_version() {
+        assert_eq!(endpoint_name("/products/get-product-activity/v1"), "get-product-activity");
+        assert_eq!(endpoint_name("/"), "request");
+    }
+
+    #[tokio::test]
+    async fn test_replays_recorded_responses_without_network() {
+        let dir = scratch_dir("replay");
+        let mock = MockPureApi::new(Fixtures::load(DEFAULT_FIXTURES_DIR).unwrap());
+        let base_url = mock.spawn().await.unwrap();
+
+        let mut config = client_config(&base_url, "postgres://unused");
+        config.pure_api_key = "secret-key-123".to_string();
+        config.cassette_mode = CassetteMode::Record;
+        config.cassette_dir = dir.clone();
+
+        // A 503 then a successful retry
+        mock.set_faults(Faults { server_errors: 1, server_error_status: Some(503), ..Default::default() });
+        let recording = PureApiClient::new(&config).unwrap();
+        let recorded = recording.fetch_product_activity("silver-maple-1oz", "silver-maple-1oz-single").await.unwrap();
+
+        let files = interaction_files(&dir).unwrap();
+        assert_eq!(files.len(), 2);
+        for (_, file) in &files {
+            assert!(!std::fs::read_to_string(file).unwrap().contains("secret-key-123"));
+        }
+
+        // Nothing listens at the replay client's base URL
+        config.api_base_url = "http://127.0.0.1:9".to_string();
+        config.cassette_mode = CassetteMode::Replay;
+        let replaying = PureApiClient::new(&config).unwrap();
+        let replayed = replaying.fetch_product_activity("silver-maple-1oz", "silver-maple-1oz-single").await.unwrap();
+        assert_eq!(replayed.len(), recorded.len());
+        assert_eq!(replayed[0].price, recorded[0].price);
+
+        let err = replaying.fetch_product_activity("gold-eagle-1oz", "gold-eagle-1oz-tube").await.unwrap_err();
+        assert_eq!(err.kind(), "not_recorded");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recording_continues_numbering() {
+        let dir = scratch_dir("numbering");
+        let recorder = Recorder::new(&dir, "key").unwrap();
+        recorder.record("/products/get-products/v1", &[("ids", "a")], StatusCode::OK, &HeaderMap::new(), b"{}");
+
+        let recorder = Recorder::new(&dir, "key").unwrap();
+        recorder.record("/products/get-products/v1", &[("ids", "a")], StatusCode::OK, &HeaderMap::new(), b"{\"data\": []}");
+
+        let seqs: Vec<_> = interaction_files(&dir).unwrap().into_iter().map(|(seq, _)| seq).collect();
+        assert_eq!(seqs, vec![Some(1), Some(2)]);
+
+        // Replay serves them in order, then repeats the last
+        let replayer = Replayer::load(&dir).unwrap();
+        let next = || replayer.next("/products/get-products/v1", &[("ids", "a")]).unwrap().body;
+        assert_eq!(next(), "{}");
+        assert_eq!(next(), "{\"data\": []}");
+        assert_eq!(next(), "{\"data\": []}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use std::pin::pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests are rejected until the cooldown has passed
+    Open,
+    /// A single probe request is allowed through to test the API
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Why a request was not allowed through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitOpen {
+    pub consecutive_failures: u32,
+    /// Time left until the next probe is allowed
+    pub retry_in: Duration,
+    pub last_error: Option<String>,
+}
+
+/// Point-in-time view of the breaker, for logs and the health endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreakerStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub opened_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// How many times the breaker has opened since startup
+    pub times_opened: u64,
+}
+
+/// Stops calling the Pure API after `failure_threshold` consecutive failures
+///
+/// Once open, requests fail immediately for `cooldown`. The first request after
+/// that is let through as a probe while any others wait for its outcome: success
+/// closes the breaker, failure opens it for another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+    probe_done: Notify,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<(Instant, DateTime<Utc>)>,
+    probe_in_flight: bool,
+    last_error: Option<String>,
+    times_opened: u64,
+}
+
+/// What a caller may do at a given moment
+#[derive(Debug, PartialEq, Eq)]
+enum Admission {
+    Allow,
+    /// Allowed through as the half-open probe
+    Probe,
+    WaitForProbe,
+    Reject(CircuitOpen),
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+                last_error: None,
+                times_opened: 0,
+            }),
+            probe_done: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits until a request may be sent, or fails if the breaker is open
+    ///
+    /// The permit must be given the request's outcome; dropping it without one
+    /// lets another caller probe instead.
+    pub async fn acquire(&self) -> Result<CircuitPermit<'_>, CircuitOpen> {
+        loop {
+            // Register for the probe's outcome before checking, so it can't be missed
+            let mut probe_done = pin!(self.probe_done.notified());
+            probe_done.as_mut().enable();
+
+            match self.admit(Instant::now()) {
+                Admission::Allow => return Ok(CircuitPermit { breaker: self, probe: false, resolved: false }),
+                Admission::Probe => return Ok(CircuitPermit { breaker: self, probe: true, resolved: false }),
+                Admission::Reject(open) => return Err(open),
+                Admission::WaitForProbe => probe_done.await,
+            }
+        }
+    }
+
+    fn admit(&self, now: Instant) -> Admission {
+        let mut inner = self.lock();
+        match inner.state {
+            CircuitState::Closed => Admission::Allow,
+            CircuitState::HalfOpen if inner.probe_in_flight => Admission::WaitForProbe,
+            CircuitState::HalfOpen => {
+                inner.probe_in_flight = true;
+                Admission::Probe
+            }
+            CircuitState::Open => {
+                let opened_at = inner.opened_at.map_or(now, |(instant, _)| instant);
+                let elapsed = now.saturating_duration_since(opened_at);
+                if elapsed >= self.cooldown {
+                    info!("Circuit breaker half-open, sending a probe request to the Pure API");
+                    inner.state = CircuitState::HalfOpen;
+                    inner.probe_in_flight = true;
+                    Admission::Probe
+                } else {
+                    Admission::Reject(CircuitOpen {
+                        consecutive_failures: inner.consecutive_failures,
+                        retry_in: self.cooldown - elapsed,
+                        last_error: inner.last_error.clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    fn on_success(&self) {
+        let mut inner = self.lock();
+        if inner.state != CircuitState::Closed {
+            info!("Circuit breaker closed, Pure API is responding again");
+        }
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        let was_probe = std::mem::take(&mut inner.probe_in_flight);
+        drop(inner);
+
+        if was_probe {
+            self.probe_done.notify_waiters();
+        }
+    }
+
+    fn on_failure(&self, now: Instant, error: &str) {
+        let mut inner = self.lock();
+        inner.consecutive_failures += 1;
+        inner.last_error = Some(error.to_string());
+
+        let was_probe = std::mem::take(&mut inner.probe_in_flight);
+        let should_open = match inner.state {
+            CircuitState::Closed => inner.consecutive_failures >= self.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+
+        if should_open {
+            warn!(
+                "Circuit breaker open after {} consecutive failures, pausing Pure API calls for {}s: {}",
+                inner.consecutive_failures, self.cooldown.as_secs(), error
+            );
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some((now, Utc::now()));
+            inner.times_opened += 1;
+        }
+        drop(inner);
+
+        if was_probe {
+            self.probe_done.notify_waiters();
+        }
+    }
+
+    /// Lets another caller probe after a probe ended without an outcome
+    fn on_abandoned(&self) {
+        self.lock().probe_in_flight = false;
+        self.probe_done.notify_waiters();
+    }
+
+    pub fn status(&self) -> CircuitBreakerStatus {
+        let inner = self.lock();
+        CircuitBreakerStatus {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            opened_at: inner.opened_at.map(|(_, at)| at),
+            last_error: inner.last_error.clone(),
+            times_opened: inner.times_opened,
+        }
+    }
+}
+
+/// Permission to send one request, to be resolved with its outcome
+#[derive(Debug)]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    resolved: bool,
+}
+
+impl CircuitPermit<'_> {
+    /// The API responded, even if with an error about this particular request
+    pub fn record_success(mut self) {
+        self.resolved = true;
+        self.breaker.on_success();
+    }
+
+    /// The API was unreachable or failing
+    pub fn record_failure(mut self, error: &str) {
+        self.resolved = true;
+        self.breaker.on_failure(Instant::now(), error);
+    }
+
+    /// The API only asked us to slow down, which says nothing about whether it
+    /// is healthy; the failure count is left alone, and a probe is handed on
+    pub fn record_neutral(self) {
+        // Dropping a probe unresolved lets another caller probe
+        drop(self);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.resolved {
+            self.breaker.on_abandoned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+
+        breaker.on_failure(start, "502");
+        breaker.on_failure(start, "502");
+        assert_eq!(breaker.admit(start), Admission::Allow);
+
+        breaker.on_failure(start, "503");
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.times_opened, 1);
+
+        let Admission::Reject(open) = breaker.admit(start + Duration::from_secs(20)) else {
+            panic!("expected the breaker to reject requests");
+        };
+        assert_eq!(open.retry_in, Duration::from_secs(40));
+        assert_eq!(open.last_error.as_deref(), Some("503"));
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        breaker.on_failure(start, "502");
+        breaker.on_success();
+        breaker.on_failure(start, "502");
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_allows_one_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+        breaker.on_failure(start, "502");
+
+        let after_cooldown = start + Duration::from_secs(60);
+        assert_eq!(breaker.admit(after_cooldown), Admission::Probe);
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        assert_eq!(breaker.admit(after_cooldown), Admission::WaitForProbe);
+
+        // A failed probe reopens the breaker for another cooldown
+        breaker.on_failure(after_cooldown, "502");
+        assert_eq!(breaker.status().state, CircuitState::Open);
+        assert!(matches!(breaker.admit(after_cooldown), Admission::Reject(_)));
+
+        // A successful probe closes it
+        let later = after_cooldown + Duration::from_secs(60);
+        assert_eq!(breaker.admit(later), Admission::Probe);
+        breaker.on_success();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert_eq!(breaker.admit(later), Admission::Allow);
+    }
+
+    #[test]
+    fn test_abandoned_probe_lets_another_caller_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+        breaker.on_failure(start, "502");
+
+        let after_cooldown = start + Duration::from_secs(60);
+        assert_eq!(breaker.admit(after_cooldown), Admission::Probe);
+        drop(CircuitPermit { breaker: &breaker, probe: true, resolved: false });
+        assert_eq!(breaker.admit(after_cooldown), Admission::Probe);
+    }
+
+    #[test]
+    fn test_neutral_outcome_leaves_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        breaker.on_failure(start, "502");
+        CircuitPermit { breaker: &breaker, probe: false, resolved: false }.record_neutral();
+        assert_eq!(breaker.status().consecutive_failures, 1);
+
+        breaker.on_failure(start, "502");
+        assert_eq!(breaker.status().state, CircuitState::Open);
+    }
+}
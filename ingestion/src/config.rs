@@ -1,5 +1,9 @@
 use anyhow::{Context, Result};
+use std::path::PathBuf;
 use std::time::Duration;
+use tracing::warn;
+
+use crate::cassette::CassetteMode;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -12,18 +16,45 @@ pub struct Config {
     pub pure_api_key: String,
     pub api_base_url: String,
 
+    // Recording and replaying Pure API responses
+    pub cassette_mode: CassetteMode,
+    pub cassette_dir: PathBuf,
+
+    // Health endpoint
+    pub health_port: u16,
+
     // Sync intervals
     pub product_sync_interval: Duration,
     pub transaction_sync_interval: Duration,
 
+    // Poll scheduling
+    pub min_poll_interval: Duration,
+    pub max_poll_interval: Duration,
+    pub activity_window: Duration,
+    pub delisted_poll_grace: Duration,
+
     // Rate limiting and retry configuration
-    pub rate_limit_delay: Duration,
+    pub api_requests_per_minute: u32,
+    pub api_burst: u32,
     pub max_retries: u32,
     pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub max_retry_after: Duration,
+    pub circuit_failure_threshold: u32,
+    pub circuit_cooldown: Duration,
+
+    // Event type classification
+    pub snapshot_max_age: Duration,
+    pub side_classifier: String,
+
+    // Concurrency
+    pub activity_fetch_concurrency: usize,
+    pub product_batch_concurrency: usize,
 
     // Batch sizes
     pub product_batch_size: usize,
     pub transaction_insert_batch_size: usize,
+    pub product_insert_batch_size: usize,
 }
 
 impl Config {
@@ -48,6 +79,18 @@ impl Config {
         let api_base_url = std::env::var("API_BASE_URL")
             .unwrap_or_else(|_| "https://api.collectpure.com".to_string());
 
+        let cassette_mode = CassetteMode::from_name(
+            &std::env::var("PURE_API_CASSETTE").unwrap_or_default()
+        ).context("PURE_API_CASSETTE must be off, record or replay")?;
+
+        let cassette_dir = std::env::var("PURE_API_CASSETTE_DIR")
+            .unwrap_or_else(|_| "cassettes/pure_api".to_string());
+
+        let health_port = std::env::var("HEALTH_PORT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(8081);
+
         let product_sync_interval_secs = std::env::var("PRODUCT_SYNC_INTERVAL_SECS")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -56,12 +99,39 @@ impl Config {
         let transaction_sync_interval_secs = std::env::var("TRANSACTION_SYNC_INTERVAL_SECS")
             .ok()
             .and_then(|v| v.parse().ok())
-            .unwrap_or(21600); // 6 hours
+            // 15 minutes, down from 6 hours when every variant was polled on every
+            // sync; now each sync only polls variants that are due, and a longer
+            // interval would leave MIN_POLL_INTERVAL_SECS unreachable
+            .unwrap_or(900);
+
+        // RATE_LIMIT_DELAY_SECS was a fixed sleep between requests; it is still
+        // honoured, as the equivalent rate, unless API_REQUESTS_PER_MINUTE is set
+        let rate_limit_delay_secs: Option<u64> = std::env::var("RATE_LIMIT_DELAY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok());
+
+        let api_requests_per_minute = match (std::env::var("API_REQUESTS_PER_MINUTE").ok(), rate_limit_delay_secs) {
+            (Some(v), delay) => {
+                if delay.is_some() {
+                    warn!("RATE_LIMIT_DELAY_SECS is ignored because API_REQUESTS_PER_MINUTE is set");
+                }
+                v.parse().unwrap_or(10)
+            }
+            (None, Some(delay_secs)) => {
+                let requests_per_minute = requests_per_minute_from_delay(delay_secs);
+                warn!(
+                    "RATE_LIMIT_DELAY_SECS is deprecated; treating a {}s delay as API_REQUESTS_PER_MINUTE={}",
+                    delay_secs, requests_per_minute
+                );
+                requests_per_minute
+            }
+            (None, None) => 10,
+        };
 
-        let rate_limit_delay_secs = std::env::var("RATE_LIMIT_DELAY_SECS")
+        let api_burst = std::env::var("API_BURST")
             .ok()
             .and_then(|v| v.parse().ok())
-            .unwrap_or(6);
+            .unwrap_or(5);
 
         let max_retries = std::env::var("MAX_RETRIES")
             .ok()
@@ -73,6 +143,64 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(6);
 
+        let max_backoff_secs = std::env::var("MAX_BACKOFF_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(120);
+
+        let max_retry_after_secs = std::env::var("MAX_RETRY_AFTER_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900); // 15 minutes
+
+        let circuit_failure_threshold = std::env::var("CIRCUIT_FAILURE_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        let circuit_cooldown_secs = std::env::var("CIRCUIT_COOLDOWN_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300); // 5 minutes
+
+        let min_poll_interval_secs = std::env::var("MIN_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900); // 15 minutes
+
+        let max_poll_interval_secs = std::env::var("MAX_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400); // 24 hours
+
+        let activity_window_secs = std::env::var("ACTIVITY_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(604800); // 7 days
+
+        let delisted_poll_grace_secs = std::env::var("DELISTED_POLL_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400); // 24 hours
+
+        let snapshot_max_age_secs = std::env::var("SNAPSHOT_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(7200); // 2 hours
+
+        let side_classifier = std::env::var("SIDE_CLASSIFIER")
+            .unwrap_or_else(|_| "nearest_quote".to_string());
+
+        let activity_fetch_concurrency = std::env::var("ACTIVITY_FETCH_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(4);
+
+        let product_batch_concurrency = std::env::var("PRODUCT_BATCH_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2);
+
         let product_batch_size = std::env::var("PRODUCT_BATCH_SIZE")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -83,19 +211,73 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(1000);
 
+        let product_insert_batch_size = std::env::var("PRODUCT_INSERT_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+
         Ok(Self {
             database_url,
             database_max_connections,
             database_acquire_timeout: Duration::from_secs(database_acquire_timeout_secs),
             pure_api_key,
             api_base_url,
+            cassette_mode,
+            cassette_dir: PathBuf::from(cassette_dir),
+            health_port,
             product_sync_interval: Duration::from_secs(product_sync_interval_secs),
             transaction_sync_interval: Duration::from_secs(transaction_sync_interval_secs),
-            rate_limit_delay: Duration::from_secs(rate_limit_delay_secs),
+            min_poll_interval: Duration::from_secs(min_poll_interval_secs),
+            max_poll_interval: Duration::from_secs(max_poll_interval_secs),
+            activity_window: Duration::from_secs(activity_window_secs),
+            delisted_poll_grace: Duration::from_secs(delisted_poll_grace_secs),
+            api_requests_per_minute,
+            api_burst,
             max_retries,
             initial_backoff: Duration::from_secs(initial_backoff_secs),
+            max_backoff: Duration::from_secs(max_backoff_secs),
+            max_retry_after: Duration::from_secs(max_retry_after_secs),
+            circuit_failure_threshold,
+            circuit_cooldown: Duration::from_secs(circuit_cooldown_secs),
+            snapshot_max_age: Duration::from_secs(snapshot_max_age_secs),
+            side_classifier,
+            activity_fetch_concurrency,
+            product_batch_concurrency,
             product_batch_size,
             transaction_insert_batch_size,
+            product_insert_batch_size,
         })
     }
+
+    /// Activity polls that fit in one transaction sync at the configured rate limit
+    pub fn polls_per_transaction_sync(&self) -> usize {
+        if self.api_requests_per_minute == 0 {
+            return usize::MAX;
+        }
+        let minutes = self.transaction_sync_interval.as_secs_f64() / 60.0;
+        (minutes * f64::from(self.api_requests_per_minute)).max(1.0) as usize
+    }
+}
+
+/// Request rate matching the old fixed `RATE_LIMIT_DELAY_SECS` sleep between
+/// requests, rounded down but never below one request a minute; a zero delay
+/// meant no limiting, which is 0 here too
+fn requests_per_minute_from_delay(delay_secs: u64) -> u32 {
+    if delay_secs == 0 {
+        return 0;
+    }
+    (60 / delay_secs).max(1) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_per_minute_from_delay() {
+        assert_eq!(requests_per_minute_from_delay(6), 10);
+        assert_eq!(requests_per_minute_from_delay(7), 8);
+        assert_eq!(requests_per_minute_from_delay(120), 1);
+        assert_eq!(requests_per_minute_from_delay(0), 0);
+    }
 }
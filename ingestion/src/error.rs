@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::circuit_breaker::CircuitOpen;
+
+/// A failed request to the Pure API, classified by whether it is worth retrying
+#[derive(Debug, Error)]
+pub enum PureApiError {
+    /// The request never got a response: connection refused, timeout, reset
+    #[error("request failed: {0}")]
+    Transport(#[source] reqwest::Error),
+
+    #[error("rate limited (429 Too Many Requests): {body}")]
+    RateLimited { retry_after: Option<Duration>, body: String },
+
+    #[error("server error ({status}): {body}")]
+    Server { status: StatusCode, retry_after: Option<Duration>, body: String },
+
+    /// A 4xx other than 429; repeating the same request won't help
+    #[error("client error ({status}): {body}")]
+    Client { status: StatusCode, body: String },
+
+    #[error("failed to decode response: {0}")]
+    Decode(#[source] serde_json::Error),
+
+    /// Not sent, because the Pure API has been failing
+    #[error(
+        "circuit breaker open after {} consecutive failures, next probe in {}s{}",
+        .0.consecutive_failures,
+        .0.retry_in.as_secs(),
+        .0.last_error.as_deref().map(|e| format!("; last error: {}", e)).unwrap_or_default()
+    )]
+    CircuitOpen(CircuitOpen),
+
+    /// Replaying a cassette that has no response for this request
+    #[error("no recorded response for {0}")]
+    NotRecorded(String),
+}
+
+impl PureApiError {
+    /// Short name of the error class, as recorded against sync run failures
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Transport(_) => "transport",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Server { .. } => "server",
+            Self::Client { .. } => "client",
+            Self::Decode(_) => "decode",
+            Self::CircuitOpen(_) => "circuit_open",
+            Self::NotRecorded(_) => "not_recorded",
+        }
+    }
+
+    /// Whether the same request may succeed if sent again
+    ///
+    /// These are also the failures that say the API itself is unavailable,
+    /// rather than something being wrong with one request.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport(e) => !e.is_builder(),
+            Self::RateLimited { .. } | Self::Server { .. } => true,
+            Self::Client { .. } | Self::Decode(_) | Self::CircuitOpen(_) | Self::NotRecorded(_) => false,
+        }
+    }
+
+    /// How long the server asked us to wait before retrying, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } | Self::Server { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Classifies a non-success status, with its `Retry-After` and body
+    fn from_status(status: StatusCode, headers: &HeaderMap, body: String) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            Self::RateLimited { retry_after: parse_retry_after(headers, Utc::now()), body }
+        } else if status.is_server_error() {
+            let retry_after = if status == StatusCode::SERVICE_UNAVAILABLE {
+                parse_retry_after(headers, Utc::now())
+            } else {
+                None
+            };
+            Self::Server { status, retry_after, body }
+        } else {
+            Self::Client { status, body }
+        }
+    }
+}
+
+/// Decodes a response body as JSON, turning error statuses into `PureApiError`
+pub fn decode_json<T: DeserializeOwned>(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Result<T, PureApiError> {
+    if !status.is_success() {
+        let body = String::from_utf8_lossy(body).into_owned();
+        return Err(PureApiError::from_status(status, headers, body));
+    }
+
+    serde_json::from_slice(body).map_err(PureApiError::Decode)
+}
+
+/// Parses `Retry-After` as either delay seconds or an HTTP date
+fn parse_retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds_and_date() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 30).unwrap();
+        assert_eq!(parse_retry_after(&headers("120"), now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT"), now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after(&headers("soon"), now), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_only_rate_limits_and_server_errors_are_retryable() {
+        let rate_limited = PureApiError::from_status(StatusCode::TOO_MANY_REQUESTS, &headers("5"), String::new());
+        assert!(rate_limited.is_retryable());
+        assert_eq!(rate_limited.retry_after(), Some(Duration::from_secs(5)));
+
+        let unavailable = PureApiError::from_status(StatusCode::SERVICE_UNAVAILABLE, &headers("7"), String::new());
+        assert!(unavailable.is_retryable());
+        assert_eq!(unavailable.retry_after(), Some(Duration::from_secs(7)));
+
+        // Retry-After is only meaningful on 429 and 503
+        let bad_gateway = PureApiError::from_status(StatusCode::BAD_GATEWAY, &headers("7"), String::new());
+        assert!(bad_gateway.is_retryable());
+        assert_eq!(bad_gateway.retry_after(), None);
+
+        let unauthorized = PureApiError::from_status(StatusCode::UNAUTHORIZED, &HeaderMap::new(), String::new());
+        assert!(!unauthorized.is_retryable());
+        assert_eq!(unauthorized.kind(), "client");
+
+        let decode = PureApiError::Decode(serde_json::from_str::<u32>("{").unwrap_err());
+        assert!(!decode.is_retryable());
+    }
+}
@@ -1,56 +1,269 @@
-/// Determines the event type (buy/sell/unknown) based on spot premium comparison
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{EventType, MarketSnapshot, UpstreamEventKind};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::side_classifier::{self, SideClassifier, TradeQuote};
+
+/// No market snapshot was captured at or before the trade
+pub const REASON_NO_SNAPSHOT: &str = "no_snapshot_before_trade";
+/// The closest snapshot before the trade is older than the configured window
+pub const REASON_STALE_SNAPSHOT: &str = "snapshot_too_old";
+/// The snapshot is missing either the highest offer or the lowest listing
+pub const REASON_MISSING_MARKET_DATA: &str = "missing_market_data";
+
+/// The side was taken from the upstream activity `event` field
+pub const SOURCE_UPSTREAM: &str = "upstream";
+/// The side was inferred by comparing the trade premium to the market
+pub const SOURCE_PREMIUM: &str = "premium";
+
+/// Result of classifying a trade, with the reason recorded when it is unknown
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub event_type: EventType,
+    pub confidence: f64,
+    pub reason: Option<String>,
+    pub source: String,
+}
+
+/// Finds the latest snapshot captured at or before `event_time`
 ///
-/// Compares the transaction's spot premium to current market data:
-/// - "buy" if transaction_premium is closer to lowest_listing_premium
-/// - "sell" if transaction_premium is closer to highest_offer_premium
-/// - "unknown" if market data is unavailable
-pub fn determine_event_type(
-    transaction_premium: f64,
-    highest_offer_premium: Option<f64>,
-    lowest_listing_premium: Option<f64>,
-) -> String {
-    match (highest_offer_premium, lowest_listing_premium) {
-        (Some(offer), Some(listing)) => {
-            let dist_to_offer = (transaction_premium - offer).abs();
-            let dist_to_listing = (transaction_premium - listing).abs();
-            if dist_to_listing < dist_to_offer {
-                "buy".to_string()
-            } else {
-                "sell".to_string()
-            }
+/// `snapshots` must be sorted by `captured_at` ascending.
+pub fn nearest_snapshot_before(
+    snapshots: &[MarketSnapshot],
+    event_time: DateTime<Utc>,
+) -> Option<&MarketSnapshot> {
+    let index = snapshots.partition_point(|s| s.captured_at <= event_time);
+    index.checked_sub(1).map(|i| &snapshots[i])
+}
+
+/// Classifies trades with a configured side classifier and snapshot window
+pub struct TradeClassifier {
+    side_classifier: Box<dyn SideClassifier>,
+    max_snapshot_age: Duration,
+}
+
+impl TradeClassifier {
+    pub fn new(side_classifier: Box<dyn SideClassifier>, max_snapshot_age: Duration) -> Self {
+        Self { side_classifier, max_snapshot_age }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self::new(
+            side_classifier::from_name(&config.side_classifier)?,
+            config.snapshot_max_age,
+        ))
+    }
+
+    pub fn max_snapshot_age(&self) -> Duration {
+        self.max_snapshot_age
+    }
+
+    /// Determines the event type against the market as it was when the trade happened
+    ///
+    /// The snapshot must have been captured no more than `max_snapshot_age` before
+    /// `event_time`; otherwise the trade is unknown and the reason is returned.
+    pub fn determine_event_type_at(
+        &self,
+        transaction_premium: f64,
+        event_time: DateTime<Utc>,
+        snapshot: Option<&MarketSnapshot>,
+        previous_premium: Option<f64>,
+    ) -> Classification {
+        let unknown = |reason: &str| Classification {
+            event_type: EventType::Unknown,
+            confidence: 0.0,
+            reason: Some(reason.to_string()),
+            source: SOURCE_PREMIUM.to_string(),
+        };
+
+        let Some(snapshot) = snapshot.filter(|s| s.captured_at <= event_time) else {
+            return unknown(REASON_NO_SNAPSHOT);
+        };
+
+        let max_age = TimeDelta::from_std(self.max_snapshot_age).unwrap_or(TimeDelta::MAX);
+        if event_time - snapshot.captured_at > max_age {
+            return unknown(REASON_STALE_SNAPSHOT);
+        }
+
+        let (Some(highest_offer), Some(lowest_listing)) =
+            (snapshot.highest_offer_spot_premium, snapshot.lowest_listing_spot_premium)
+        else {
+            return unknown(REASON_MISSING_MARKET_DATA);
+        };
+
+        let estimate = self.side_classifier.classify(&TradeQuote {
+            premium: transaction_premium,
+            highest_offer,
+            lowest_listing,
+            previous_premium,
+        });
+
+        Classification {
+            event_type: estimate.side,
+            confidence: estimate.confidence,
+            reason: None,
+            source: SOURCE_PREMIUM.to_string(),
+        }
+    }
+
+    /// Classifies a trade, trusting the upstream event kind when it documents a side
+    ///
+    /// Falls back to the premium heuristic in `determine_event_type_at` otherwise,
+    /// which for now is every trade; see `UpstreamEventKind::from_raw`.
+    pub fn classify(
+        &self,
+        upstream_event: &str,
+        transaction_premium: f64,
+        event_time: DateTime<Utc>,
+        snapshot: Option<&MarketSnapshot>,
+        previous_premium: Option<f64>,
+    ) -> Classification {
+        match UpstreamEventKind::from_raw(upstream_event).side() {
+            Some(side) => Classification {
+                event_type: side,
+                confidence: 1.0,
+                reason: None,
+                source: SOURCE_UPSTREAM.to_string(),
+            },
+            None => self.determine_event_type_at(transaction_premium, event_time, snapshot, previous_premium),
         }
-        _ => "unknown".to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::side_classifier::NearestQuote;
+
+    fn classifier() -> TradeClassifier {
+        TradeClassifier::new(Box::new(NearestQuote), Duration::from_secs(3600))
+    }
+
+    fn snapshot_at(captured_at: DateTime<Utc>, offer: Option<f64>, listing: Option<f64>) -> MarketSnapshot {
+        MarketSnapshot {
+            id: 1,
+            product_id: 1,
+            pure_product_id: "product".to_string(),
+            pure_variant_id: "variant".to_string(),
+            highest_offer_spot_premium: offer,
+            lowest_listing_spot_premium: listing,
+            captured_at,
+        }
+    }
+
+    /// Classifies `premium` against a fresh snapshot with the given quotes
+    fn classify_against(premium: f64, offer: Option<f64>, listing: Option<f64>) -> Classification {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::minutes(5), offer, listing);
+        classifier().determine_event_type_at(premium, now, Some(&snapshot), None)
+    }
 
     #[test]
     fn test_closer_to_listing_is_buy() {
         // Premium of 5.0 is closer to listing (6.0) than offer (2.0)
-        assert_eq!(determine_event_type(5.0, Some(2.0), Some(6.0)), "buy");
+        assert_eq!(classify_against(5.0, Some(2.0), Some(6.0)).event_type, EventType::Buy);
     }
 
     #[test]
     fn test_closer_to_offer_is_sell() {
         // Premium of 3.0 is closer to offer (2.0) than listing (6.0)
-        assert_eq!(determine_event_type(3.0, Some(2.0), Some(6.0)), "sell");
+        assert_eq!(classify_against(3.0, Some(2.0), Some(6.0)).event_type, EventType::Sell);
     }
 
     #[test]
     fn test_missing_offer() {
-        assert_eq!(determine_event_type(5.0, None, Some(6.0)), "unknown");
+        let classification = classify_against(5.0, None, Some(6.0));
+        assert_eq!(classification.event_type, EventType::Unknown);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_MISSING_MARKET_DATA));
     }
 
     #[test]
     fn test_missing_listing() {
-        assert_eq!(determine_event_type(5.0, Some(2.0), None), "unknown");
+        let classification = classify_against(5.0, Some(2.0), None);
+        assert_eq!(classification.event_type, EventType::Unknown);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_MISSING_MARKET_DATA));
     }
 
     #[test]
     fn test_both_missing() {
-        assert_eq!(determine_event_type(5.0, None, None), "unknown");
+        let classification = classify_against(5.0, None, None);
+        assert_eq!(classification.event_type, EventType::Unknown);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_MISSING_MARKET_DATA));
+    }
+
+    #[test]
+    fn test_nearest_snapshot_before_picks_latest_prior() {
+        let now = Utc::now();
+        let snapshots = vec![
+            snapshot_at(now - TimeDelta::hours(3), Some(1.0), Some(2.0)),
+            snapshot_at(now - TimeDelta::hours(2), Some(3.0), Some(4.0)),
+            snapshot_at(now + TimeDelta::hours(1), Some(5.0), Some(6.0)),
+        ];
+
+        let nearest = nearest_snapshot_before(&snapshots, now).unwrap();
+        assert_eq!(nearest.captured_at, now - TimeDelta::hours(2));
+        assert!(nearest_snapshot_before(&snapshots, now - TimeDelta::hours(4)).is_none());
+    }
+
+    #[test]
+    fn test_classifies_against_snapshot_in_window() {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::minutes(30), Some(2.0), Some(6.0));
+
+        let classification = classifier().determine_event_type_at(5.0, now, Some(&snapshot), None);
+        assert_eq!(classification.event_type, EventType::Buy);
+        assert_eq!(classification.confidence, 0.5);
+        assert_eq!(classification.reason, None);
+        assert_eq!(classification.source, SOURCE_PREMIUM);
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_unknown() {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::hours(2), Some(2.0), Some(6.0));
+
+        let classification = classifier().determine_event_type_at(5.0, now, Some(&snapshot), None);
+        assert_eq!(classification.event_type, EventType::Unknown);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_STALE_SNAPSHOT));
+    }
+
+    #[test]
+    fn test_missing_snapshot_is_unknown() {
+        let classification = classifier().determine_event_type_at(5.0, Utc::now(), None, None);
+        assert_eq!(classification.event_type, EventType::Unknown);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_NO_SNAPSHOT));
+    }
+
+    #[test]
+    fn test_snapshot_without_market_data_is_unknown() {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::minutes(5), None, Some(6.0));
+
+        let classification = classifier().determine_event_type_at(5.0, now, Some(&snapshot), None);
+        assert_eq!(classification.reason.as_deref(), Some(REASON_MISSING_MARKET_DATA));
+    }
+
+    #[test]
+    fn test_undocumented_upstream_kinds_fall_back_to_premium() {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::minutes(5), Some(2.0), Some(6.0));
+
+        // "Sold" could mean either side, so the premium at the listing decides
+        let classification = classifier().classify("Sold", 6.0, now, Some(&snapshot), None);
+        assert_eq!(classification.event_type, EventType::Buy);
+        assert_eq!(classification.source, SOURCE_PREMIUM);
+    }
+
+    #[test]
+    fn test_sideless_upstream_falls_back_to_premium() {
+        let now = Utc::now();
+        let snapshot = snapshot_at(now - TimeDelta::minutes(5), Some(2.0), Some(6.0));
+
+        let classification = classifier().classify("sale", 6.0, now, Some(&snapshot), None);
+        assert_eq!(classification.event_type, EventType::Buy);
+        assert_eq!(classification.confidence, 1.0);
+        assert_eq!(classification.source, SOURCE_PREMIUM);
     }
 }
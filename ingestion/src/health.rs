@@ -0,0 +1,49 @@
+use anyhow::Result;
+use axum::{extract::State, routing::get, Json, Router};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::circuit_breaker::CircuitState;
+use crate::pure_api::PureApiClient;
+
+/// Serves `GET /health` on `port`, reporting the Pure API client's state
+pub async fn serve(port: u16, client: Arc<PureApiClient>) -> Result<()> {
+    let app = Router::new()
+        .route("/health", get(health_check))
+        .with_state(client);
+
+    let addr = format!("0.0.0.0:{}", port);
+    info!("Ingestion health endpoint listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+async fn health_check(State(client): State<Arc<PureApiClient>>) -> Json<Value> {
+    let circuit = client.circuit_breaker_status();
+    let rate_limit = client.rate_limit_stats();
+
+    // Still "ok" to the platform: restarting ingestion won't bring the Pure API back
+    let status = if circuit.state == CircuitState::Closed { "ok" } else { "degraded" };
+
+    Json(json!({
+        "status": status,
+        "service": "pure-trading-ingestion",
+        "circuit_breaker": {
+            "state": circuit.state.as_str(),
+            "consecutive_failures": circuit.consecutive_failures,
+            "opened_at": circuit.opened_at,
+            "times_opened": circuit.times_opened,
+            "last_error": circuit.last_error,
+        },
+        "rate_limiter": {
+            "requests": rate_limit.acquired,
+            "requests_waited": rate_limit.waited,
+            "total_wait_secs": rate_limit.total_wait.as_secs_f64(),
+            "max_wait_secs": rate_limit.max_wait.as_secs_f64(),
+        },
+    }))
+}
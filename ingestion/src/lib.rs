@@ -1,4 +1,19 @@
+pub mod cassette;
+pub mod circuit_breaker;
 pub mod config;
+pub mod error;
 pub mod event_type;
+pub mod health;
+pub mod listings;
+pub mod market_snapshots;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock_pure_api;
+pub mod product_history;
 pub mod pure_api;
+pub mod rate_limiter;
 pub mod retry;
+pub mod scheduler;
+pub mod side_classifier;
+pub mod sync;
+pub mod sync_runs;
+pub mod watermarks;
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+/// Variants whose listing status changed in one product sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListingChanges {
+    pub delisted: u64,
+    pub relisted: u64,
+}
+
+/// Marks stored variants missing from `listed` as delisted, and clears the
+/// mark on any that are listed again
+///
+/// `listed` is every (product ID, variant ID) in Pure's product options. An
+/// empty list is taken as a bad response rather than everything being delisted.
+pub async fn record_listings(pool: &PgPool, listed: &[(String, String)]) -> Result<ListingChanges> {
+    if listed.is_empty() {
+        warn!("Product options listed no variants, leaving listing status unchanged");
+        return Ok(ListingChanges::default());
+    }
+
+    let product_ids: Vec<&str> = listed.iter().map(|(product_id, _)| product_id.as_str()).collect();
+    let variant_ids: Vec<&str> = listed.iter().map(|(_, variant_id)| variant_id.as_str()).collect();
+
+    let delisted = sqlx::query(
+        r#"
+        UPDATE products p
+        SET delisted_at = NOW(),
+            updated_at = NOW()
+        WHERE p.delisted_at IS NULL
+          AND NOT EXISTS (
+              SELECT 1
+              FROM UNNEST($1::VARCHAR[], $2::VARCHAR[]) AS l(pure_product_id, pure_variant_id)
+              WHERE l.pure_product_id = p.pure_product_id
+                AND l.pure_variant_id = p.pure_variant_id
+          )
+        "#
+    )
+    .bind(&product_ids)
+    .bind(&variant_ids)
+    .execute(pool)
+    .await?;
+
+    let relisted = sqlx::query(
+        r#"
+        UPDATE products p
+        SET delisted_at = NULL,
+            updated_at = NOW()
+        FROM UNNEST($1::VARCHAR[], $2::VARCHAR[]) AS l(pure_product_id, pure_variant_id)
+        WHERE p.delisted_at IS NOT NULL
+          AND l.pure_product_id = p.pure_product_id
+          AND l.pure_variant_id = p.pure_variant_id
+        "#
+    )
+    .bind(&product_ids)
+    .bind(&variant_ids)
+    .execute(pool)
+    .await?;
+
+    let changes = ListingChanges {
+        delisted: delisted.rows_affected(),
+        relisted: relisted.rows_affected(),
+    };
+    info!("Listing status: {} variants delisted, {} relisted", changes.delisted, changes.relisted);
+
+    Ok(changes)
+}
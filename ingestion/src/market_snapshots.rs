@@ -0,0 +1,65 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use common::{MarketSnapshot, NewProduct};
+use sqlx::PgPool;
+use tracing::info;
+
+/// Appends the current bid/ask premiums of each product to the market snapshot history,
+/// all captured now
+pub async fn insert_market_snapshots(pool: &PgPool, products: &[NewProduct]) -> Result<()> {
+    info!("Recording market snapshots for {} products", products.len());
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO market_snapshots (
+            product_id,
+            pure_product_id,
+            pure_variant_id,
+            highest_offer_spot_premium,
+            lowest_listing_spot_premium,
+            captured_at
+        )
+        SELECT p.id, p.pure_product_id, p.pure_variant_id, s.highest_offer_spot_premium, s.lowest_listing_spot_premium, NOW()
+        FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::FLOAT8[], $4::FLOAT8[])
+            AS s(pure_product_id, pure_variant_id, highest_offer_spot_premium, lowest_listing_spot_premium)
+        INNER JOIN products p
+            ON p.pure_product_id = s.pure_product_id AND p.pure_variant_id = s.pure_variant_id
+        "#
+    )
+    .bind(products.iter().map(|p| p.pure_product_id.as_str()).collect::<Vec<_>>())
+    .bind(products.iter().map(|p| p.pure_variant_id.as_str()).collect::<Vec<_>>())
+    .bind(products.iter().map(|p| p.highest_offer_spot_premium).collect::<Vec<_>>())
+    .bind(products.iter().map(|p| p.lowest_listing_spot_premium).collect::<Vec<_>>())
+    .execute(pool)
+    .await?;
+
+    info!("Successfully recorded {} market snapshots", result.rows_affected());
+
+    Ok(())
+}
+
+/// Fetches the snapshots of a variant captured within `[from, to]`, oldest first
+pub async fn fetch_snapshots_between(
+    pool: &PgPool,
+    pure_product_id: &str,
+    pure_variant_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<MarketSnapshot>> {
+    Ok(sqlx::query_as::<_, MarketSnapshot>(
+        r#"
+        SELECT *
+        FROM market_snapshots
+        WHERE pure_product_id = $1
+            AND pure_variant_id = $2
+            AND captured_at BETWEEN $3 AND $4
+        ORDER BY captured_at
+        "#
+    )
+    .bind(pure_product_id)
+    .bind(pure_variant_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?)
+}
@@ -0,0 +1,450 @@
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::cassette::CassetteMode;
+use crate::config::Config;
+use crate::pure_api::{GET_PRODUCTS_PATH, PRODUCT_ACTIVITY_PATH, PRODUCT_OPTIONS_PATH};
+
+
+/// Fixtures checked in alongside the ingestion crate
+pub const DEFAULT_FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/pure_api");
+
+/// Data served by the mock, as the Pure API would return it
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+    /// Full body of the product options response
+    pub product_options: Value,
+    /// Every product the get products endpoint knows about
+    pub products: Vec<Value>,
+    /// Activity events by product ID, then variant ID
+    pub activity: HashMap<String, HashMap<String, Vec<Value>>>,
+}
+
+impl Fixtures {
+    /// Loads `product_options.json`, `products.json` and `product_activity.json` from `dir`
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+
+        let product_options = read_fixture(&dir.join("product_options.json"))?;
+
+        let products = read_fixture(&dir.join("products.json"))?
+            .get("data")
+            .and_then(Value::as_array)
+            .cloned()
+            .context("products.json must have a `data` array")?;
+
+        let activity = serde_json::from_value(read_fixture(&dir.join("product_activity.json"))?)
+            .context("product_activity.json must map product IDs to variant IDs to event arrays")?;
+
+        Ok(Self { product_options, products, activity })
+    }
+
+    fn products_by_id(&self, ids: &[&str]) -> Vec<Value> {
+        self.products
+            .iter()
+            .filter(|product| {
+                product.get("id").and_then(Value::as_str).is_some_and(|id| ids.contains(&id))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn activity_for(&self, product_id: &str, variant_id: &str) -> Vec<Value> {
+        self.activity
+            .get(product_id)
+            .and_then(|variants| variants.get(variant_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+fn read_fixture(path: &Path) -> Result<Value> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read fixture {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse fixture {}", path.display()))
+}
+
+/// Failures to inject into responses
+///
+/// The counts are used up one request at a time, in the order rate limits,
+/// server errors, malformed bodies. The delay applies to every matching request
+/// until it is changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    /// Respond 429 Too Many Requests to this many requests
+    pub rate_limited: u32,
+    /// Respond with `server_error_status` to this many requests
+    pub server_errors: u32,
+    /// Status of injected server errors (default 500)
+    pub server_error_status: Option<u16>,
+    /// `Retry-After` seconds sent with injected 429s and 503s
+    pub retry_after_secs: Option<u64>,
+    /// Respond 200 with a truncated JSON body to this many requests
+    pub malformed: u32,
+    /// Delay before responding
+    pub delay_ms: u64,
+    /// Only affect requests to this path
+    pub path: Option<String>,
+    /// Only affect requests naming this product, in `ids` or `productId`
+    pub product_id: Option<String>,
+}
+
+impl Faults {
+    fn applies_to(&self, path: &str, product_ids: &[&str]) -> bool {
+        self.path.as_deref().is_none_or(|p| p == path)
+            && self.product_id.as_deref().is_none_or(|id| product_ids.contains(&id))
+    }
+
+    /// Uses up the next injected failure, if any
+    fn take(&mut self) -> Option<Fault> {
+        if self.rate_limited > 0 {
+            self.rate_limited -= 1;
+            return Some(Fault::RateLimited);
+        }
+        if self.server_errors > 0 {
+            self.server_errors -= 1;
+            let status = self
+                .server_error_status
+                .and_then(|status| StatusCode::from_u16(status).ok())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Some(Fault::Status(status));
+        }
+        if self.malformed > 0 {
+            self.malformed -= 1;
+            return Some(Fault::Malformed);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    RateLimited,
+    Status(StatusCode),
+    Malformed,
+}
+
+/// A request the mock received
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecordedRequest {
+    pub path: String,
+    pub query: Option<String>,
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    fixtures: RwLock<Fixtures>,
+    faults: Mutex<Faults>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+/// In-process stand-in for the Pure API, serving fixtures with optional faults
+///
+/// Clones share the same fixtures, faults and request log, so a test can keep
+/// one to steer the server it spawned.
+#[derive(Debug, Clone, Default)]
+pub struct MockPureApi {
+    state: Arc<MockState>,
+}
+
+impl MockPureApi {
+    pub fn new(fixtures: Fixtures) -> Self {
+        Self {
+            state: Arc::new(MockState {
+                fixtures: RwLock::new(fixtures),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The Pure API endpoints, plus `/mock/faults` and `/mock/requests` for
+    /// steering the server over HTTP
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(PRODUCT_OPTIONS_PATH, get(product_options))
+            .route(GET_PRODUCTS_PATH, get(products))
+            .route(PRODUCT_ACTIVITY_PATH, get(product_activity))
+            .route("/mock/faults", get(get_faults).put(put_faults))
+            .route("/mock/requests", get(get_requests).delete(clear_requests))
+            .with_state(self.clone())
+    }
+
+    /// Serves on `listener` until the process exits
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    /// Serves on a free localhost port in the background, returning its base URL
+    pub async fn spawn(&self) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let mock = self.clone();
+        tokio::spawn(async move { mock.serve(listener).await });
+        Ok(format!("http://{}", addr))
+    }
+
+    pub fn faults(&self) -> Faults {
+        lock(&self.state.faults).clone()
+    }
+
+    pub fn set_faults(&self, faults: Faults) {
+        *lock(&self.state.faults) = faults;
+    }
+
+    /// Changes the fixtures served from now on
+    pub fn update_fixtures(&self, update: impl FnOnce(&mut Fixtures)) {
+        let mut fixtures = self.state.fixtures.write().unwrap_or_else(|e| e.into_inner());
+        update(&mut fixtures);
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        lock(&self.state.requests).clone()
+    }
+
+    /// How many requests have been made to `path`
+    pub fn request_count(&self, path: &str) -> usize {
+        lock(&self.state.requests).iter().filter(|r| r.path == path).count()
+    }
+
+    fn fixtures(&self) -> std::sync::RwLockReadGuard<'_, Fixtures> {
+        self.state.fixtures.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the request, then responds with `body` or an injected fault
+    async fn respond(&self, uri: &Uri, headers: &HeaderMap, product_ids: &[&str], body: Value) -> Response {
+        let path = uri.path();
+        lock(&self.state.requests).push(RecordedRequest {
+            path: path.to_string(),
+            query: uri.query().map(str::to_string),
+            api_key: headers.get("x-api-key").and_then(|v| v.to_str().ok()).map(str::to_string),
+        });
+
+        let (delay, retry_after, fault) = {
+            let mut faults = lock(&self.state.faults);
+            if faults.applies_to(path, product_ids) {
+                (Duration::from_millis(faults.delay_ms), faults.retry_after_secs, faults.take())
+            } else {
+                (Duration::ZERO, None, None)
+            }
+        };
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        match fault {
+            None => Json(body).into_response(),
+            Some(Fault::RateLimited) => {
+                with_retry_after(StatusCode::TOO_MANY_REQUESTS, retry_after, "Too many requests")
+            }
+            Some(Fault::Status(status)) => {
+                let retry_after = retry_after.filter(|_| status == StatusCode::SERVICE_UNAVAILABLE);
+                with_retry_after(status, retry_after, "Injected server error")
+            }
+            Some(Fault::Malformed) => {
+                let mut bytes = body.to_string().into_bytes();
+                bytes.truncate(bytes.len() / 2);
+                ([(header::CONTENT_TYPE, "application/json")], Body::from(bytes)).into_response()
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn with_retry_after(status: StatusCode, retry_after: Option<u64>, message: &str) -> Response {
+    let mut response = (status, Json(json!({ "error": message }))).into_response();
+    if let Some(seconds) = retry_after {
+        response.headers_mut().insert(header::RETRY_AFTER, seconds.into());
+    }
+    response
+}
+
+fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": message }))).into_response()
+}
+
+async fn product_options(State(mock): State<MockPureApi>, uri: Uri, headers: HeaderMap) -> Response {
+    let body = mock.fixtures().product_options.clone();
+    mock.respond(&uri, &headers, &[], body).await
+}
+
+async fn products(
+    State(mock): State<MockPureApi>,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(ids) = params.get("ids") else {
+        return bad_request("ids is required");
+    };
+    let ids: Vec<&str> = ids.split(',').map(str::trim).collect();
+
+    let body = json!({ "data": mock.fixtures().products_by_id(&ids) });
+    mock.respond(&uri, &headers, &ids, body).await
+}
+
+async fn product_activity(
+    State(mock): State<MockPureApi>,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let (Some(product_id), Some(variant_id)) = (params.get("productId"), params.get("variantId")) else {
+        return bad_request("productId and variantId are required");
+    };
+
+    let body = json!({ "data": mock.fixtures().activity_for(product_id, variant_id) });
+    mock.respond(&uri, &headers, &[product_id.as_str()], body).await
+}
+
+async fn get_faults(State(mock): State<MockPureApi>) -> Json<Faults> {
+    Json(mock.faults())
+}
+
+async fn put_faults(State(mock): State<MockPureApi>, Json(faults): Json<Faults>) -> Json<Faults> {
+    info!("Mock Pure API faults set to {:?}", faults);
+    mock.set_faults(faults.clone());
+    Json(faults)
+}
+
+async fn get_requests(State(mock): State<MockPureApi>) -> Json<Vec<RecordedRequest>> {
+    Json(mock.requests())
+}
+
+async fn clear_requests(State(mock): State<MockPureApi>) -> StatusCode {
+    lock(&mock.state.requests).clear();
+    StatusCode::NO_CONTENT
+}
+
+/// Client configuration pointed at a mock, with retries and rate limiting
+/// scaled down so failures surface in milliseconds
+pub fn client_config(api_base_url: &str, database_url: &str) -> Config {
+    Config {
+        database_url: database_url.to_string(),
+        database_max_connections: 5,
+        database_acquire_timeout: Duration::from_secs(3),
+        pure_api_key: "mock-api-key".to_string(),
+        api_base_url: api_base_url.to_string(),
+        cassette_mode: CassetteMode::Off,
+        cassette_dir: PathBuf::from("cassettes/pure_api"),
+        health_port: 0,
+        product_sync_interval: Duration::from_secs(3600),
+        transaction_sync_interval: Duration::from_secs(900),
+        min_poll_interval: Duration::from_secs(900),
+        max_poll_interval: Duration::from_secs(86400),
+        activity_window: Duration::from_secs(604800),
+        delisted_poll_grace: Duration::from_secs(86400),
+        api_requests_per_minute: 0,
+        api_burst: 1,
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+        max_retry_after: Duration::from_secs(5),
+        circuit_failure_threshold: 5,
+        circuit_cooldown: Duration::from_secs(300),
+        snapshot_max_age: Duration::from_secs(7200),
+        side_classifier: "nearest_quote".to_string(),
+        activity_fetch_concurrency: 4,
+        product_batch_concurrency: 2,
+        product_batch_size: 30,
+        transaction_insert_batch_size: 1000,
+        product_insert_batch_size: 1000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PureApiError;
+    use crate::pure_api::PureApiClient;
+
+    async fn start() -> (MockPureApi, PureApiClient) {
+        let mock = MockPureApi::new(Fixtures::load(DEFAULT_FIXTURES_DIR).unwrap());
+        let base_url = mock.spawn().await.unwrap();
+        let client = PureApiClient::new(&client_config(&base_url, "postgres://unused")).unwrap();
+        (mock, client)
+    }
+
+    #[tokio::test]
+    async fn test_builds_catalog_from_fixtures() {
+        let (mock, client) = start().await;
+
+        let catalog = client.build_new_products().await.unwrap();
+        assert_eq!(catalog.products.len(), 4);
+        assert_eq!(catalog.products_attempted, 3);
+        assert!(catalog.failed_batches.is_empty());
+
+        let tube = catalog.products.iter().find(|p| p.pure_variant_id == "gold-eagle-1oz-tube").unwrap();
+        assert_eq!(tube.highest_offer_spot_premium, None);
+        assert_eq!(tube.lowest_listing_spot_premium, Some(3.2));
+        // Falls back to the product image when the variant has none
+        assert_eq!(tube.image_url.as_deref(), Some("https://images.example.com/gold-eagle-1oz.png"));
+
+        let requests = mock.requests();
+        assert!(requests.iter().all(|r| r.api_key.as_deref() == Some("mock-api-key")));
+    }
+
+    #[tokio::test]
+    async fn test_retries_rate_limits_and_server_errors() {
+        let (mock, client) = start().await;
+        mock.set_faults(Faults {
+            rate_limited: 1,
+            server_errors: 1,
+            server_error_status: Some(503),
+            path: Some(PRODUCT_ACTIVITY_PATH.to_string()),
+            ..Default::default()
+        });
+
+        let events = client.fetch_product_activity("silver-maple-1oz", "silver-maple-1oz-single").await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(mock.request_count(PRODUCT_ACTIVITY_PATH), 3);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_is_not_retried() {
+        let (mock, client) = start().await;
+        mock.set_faults(Faults { malformed: 1, ..Default::default() });
+
+        let err = client.fetch_and_flatten_variants().await.unwrap_err();
+        assert!(matches!(err, PureApiError::Decode(_)));
+        assert_eq!(mock.request_count(PRODUCT_OPTIONS_PATH), 1);
+    }
+
+    #[tokio::test]
+    async fn test_faults_can_target_one_product() {
+        let (mock, client) = start().await;
+        mock.set_faults(Faults {
+            server_errors: u32::MAX,
+            product_id: Some("gold-eagle-1oz".to_string()),
+            ..Default::default()
+        });
+
+        let err = client.fetch_product_activity("gold-eagle-1oz", "gold-eagle-1oz-single").await.unwrap_err();
+        assert_eq!(err.kind(), "server");
+
+        let events = client.fetch_product_activity("silver-maple-1oz", "silver-maple-1oz-single").await.unwrap();
+        assert_eq!(events.len(), 2);
+    }
+}
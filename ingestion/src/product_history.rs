@@ -0,0 +1,89 @@
+use anyhow::Result;
+use sqlx::PgConnection;
+
+/// Versions the catalog fields of `product_ids` as they are now stored in `products`
+///
+/// A product whose name, SKU, material, variant label or image differs from its
+/// current version has that version closed and a new one opened, listing the
+/// fields that changed. A product with no history gets its first version. Run
+/// in the transaction that wrote the products, so a version is never missed.
+/// Returns how many products changed.
+pub async fn record_product_history(conn: &mut PgConnection, product_ids: &[i64]) -> Result<u64> {
+    if product_ids.is_empty() {
+        return Ok(0);
+    }
+
+    // The closed versions are carried straight into the insert, so each new
+    // version is compared against exactly the one it replaces
+    let changed: i64 = sqlx::query_scalar(
+        r#"
+        WITH closed AS (
+            UPDATE product_history h
+            SET valid_to = NOW()
+            FROM products p
+            WHERE h.product_id = p.id
+              AND h.valid_to IS NULL
+              AND p.id = ANY($1)
+              AND (h.name, h.sku, h.material, h.variant_label, h.image_url)
+                  IS DISTINCT FROM (p.name, p.sku, p.material, p.variant_label, p.image_url)
+            RETURNING h.product_id, h.name, h.sku, h.material, h.variant_label, h.image_url
+        ),
+        opened AS (
+            INSERT INTO product_history (
+                product_id,
+                pure_product_id,
+                pure_variant_id,
+                name,
+                sku,
+                material,
+                variant_label,
+                image_url,
+                changed_fields,
+                valid_from
+            )
+            SELECT
+                p.id,
+                p.pure_product_id,
+                p.pure_variant_id,
+                p.name,
+                p.sku,
+                p.material,
+                p.variant_label,
+                p.image_url,
+                ARRAY_REMOVE(ARRAY[
+                    CASE WHEN prev.name IS DISTINCT FROM p.name THEN 'name' END,
+                    CASE WHEN prev.sku IS DISTINCT FROM p.sku THEN 'sku' END,
+                    CASE WHEN prev.material IS DISTINCT FROM p.material THEN 'material' END,
+                    CASE WHEN prev.variant_label IS DISTINCT FROM p.variant_label THEN 'variant_label' END,
+                    CASE WHEN prev.image_url IS DISTINCT FROM p.image_url THEN 'image_url' END
+                ]::TEXT[], NULL),
+                NOW()
+            FROM products p
+            JOIN closed prev ON prev.product_id = p.id
+
+            UNION ALL
+
+            SELECT
+                p.id,
+                p.pure_product_id,
+                p.pure_variant_id,
+                p.name,
+                p.sku,
+                p.material,
+                p.variant_label,
+                p.image_url,
+                '{}'::TEXT[],
+                NOW()
+            FROM products p
+            WHERE p.id = ANY($1)
+              AND NOT EXISTS (SELECT 1 FROM product_history h WHERE h.product_id = p.id)
+        )
+        SELECT COUNT(*) FROM closed
+        "#
+    )
+    .bind(product_ids)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(changed as u64)
+}
@@ -1,16 +1,22 @@
 use anyhow::Result;
-use common::NewProduct;
+use common::{Material, Money, NewProduct};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 use tracing::info;
 
+use crate::cassette::{CassetteMode, Recorder, Replayer};
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerStatus};
 use crate::config::Config;
+use crate::error::{decode_json, PureApiError};
+use crate::rate_limiter::{RateLimiter, RateLimiterStats};
 use crate::retry::{with_retry_and_rate_limit, RetryConfig};
 
-const PRODUCT_OPTIONS_PATH: &str = "/products/get-product-options/v1";
-const GET_PRODUCTS_PATH: &str = "/products/get-products/v1";
-const PRODUCT_ACTIVITY_PATH: &str = "/products/get-product-activity/v1";
+pub(crate) const PRODUCT_OPTIONS_PATH: &str = "/products/get-product-options/v1";
+pub(crate) const GET_PRODUCTS_PATH: &str = "/products/get-products/v1";
+pub(crate) const PRODUCT_ACTIVITY_PATH: &str = "/products/get-product-activity/v1";
 
 // API Response structures
 #[derive(Debug, Deserialize)]
@@ -82,18 +88,48 @@ pub struct ProductActivityResponse {
 pub struct ActivityEvent {
     pub event: String,
     pub created_at: String,  // Will parse this to DateTime
-    pub price: f64,
+    pub price: Money,
     pub quantity: i32,
     pub spot_premium: f64,
-    pub spot_premium_dollar: f64,
+    pub spot_premium_dollar: Money,
+}
+
+/// A batch of product IDs that could not be fetched, even after retries
+#[derive(Debug, Clone)]
+pub struct FailedBatch {
+    pub product_ids: Vec<String>,
+    pub error_kind: &'static str,
+    pub error: String,
+}
+
+/// Result of the product building pipeline
+#[derive(Debug, Clone)]
+pub struct ProductCatalog {
+    pub products: Vec<NewProduct>,
+    /// Number of unique upstream products that were requested
+    pub products_attempted: usize,
+    pub failed_batches: Vec<FailedBatch>,
+    /// (product ID, variant ID) of every variant in the product options,
+    /// including those whose product details could not be fetched
+    pub listed_variants: Vec<(String, String)>,
+}
+
+/// Where the client's responses come from, besides the network
+enum Cassette {
+    Record(Recorder),
+    Replay(Replayer),
 }
 
 pub struct PureApiClient {
     client: Client,
     api_key: String,
     api_base_url: String,
+    cassette: Option<Cassette>,
     retry_config: RetryConfig,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
     product_batch_size: usize,
+    product_batch_concurrency: usize,
 }
 
 impl PureApiClient {
@@ -103,43 +139,88 @@ impl PureApiClient {
         let retry_config = RetryConfig {
             max_retries: config.max_retries,
             initial_backoff: config.initial_backoff,
-            rate_limit_delay: config.rate_limit_delay,
+            max_backoff: config.max_backoff,
+            max_retry_after: config.max_retry_after,
+        };
+
+        let cassette = match config.cassette_mode {
+            CassetteMode::Off => None,
+            CassetteMode::Record => Some(Cassette::Record(Recorder::new(&config.cassette_dir, &config.pure_api_key)?)),
+            CassetteMode::Replay => Some(Cassette::Replay(Replayer::load(&config.cassette_dir)?)),
+        };
+
+        // Replayed responses cost the API nothing
+        let requests_per_minute = match config.cassette_mode {
+            CassetteMode::Replay => 0,
+            _ => config.api_requests_per_minute,
         };
 
         Ok(Self {
             client,
             api_key: config.pure_api_key.clone(),
             api_base_url: config.api_base_url.clone(),
+            cassette,
             retry_config,
+            rate_limiter: RateLimiter::new(requests_per_minute, config.api_burst),
+            circuit_breaker: CircuitBreaker::new(config.circuit_failure_threshold, config.circuit_cooldown),
             product_batch_size: config.product_batch_size,
+            product_batch_concurrency: config.product_batch_concurrency,
         })
     }
 
-    /// Fetches product options from the API (without retry wrapper since it's a simple call)
-    async fn fetch_product_options_raw(&self) -> Result<ProductOptionsResponse> {
-        let url = format!("{}{}", self.api_base_url, PRODUCT_OPTIONS_PATH);
+    /// Time spent waiting on the rate limiter so far
+    pub fn rate_limit_stats(&self) -> RateLimiterStats {
+        self.rate_limiter.stats()
+    }
+
+    pub fn circuit_breaker_status(&self) -> CircuitBreakerStatus {
+        self.circuit_breaker.status()
+    }
+
+    /// Sends one GET request and decodes its JSON response
+    ///
+    /// In record mode the response is also saved to the cassette; in replay
+    /// mode it comes from the cassette and nothing is sent.
+    async fn get_json<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T, PureApiError> {
+        if let Some(Cassette::Replay(replayer)) = &self.cassette {
+            let recorded = replayer.next(path, query)?;
+            return decode_json(recorded.status(), &recorded.header_map(), recorded.body.as_bytes());
+        }
+
+        let url = format!("{}{}", self.api_base_url, path);
         let response = self.client
             .get(&url)
             .header("x-api-key", &self.api_key)
+            .query(query)
             .send()
-            .await?;
+            .await
+            .map_err(PureApiError::Transport)?;
 
         let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!("API request failed with status {}: {}", status, body));
+        let headers = response.headers().clone();
+        let body = response.bytes().await.map_err(PureApiError::Transport)?;
+
+        if let Some(Cassette::Record(recorder)) = &self.cassette {
+            recorder.record(path, query, status, &headers, &body);
         }
 
-        Ok(response.json().await?)
+        decode_json(status, &headers, &body)
+    }
+
+    /// Fetches product options from the API (without retry wrapper since it's a simple call)
+    async fn fetch_product_options_raw(&self) -> Result<ProductOptionsResponse, PureApiError> {
+        self.get_json(PRODUCT_OPTIONS_PATH, &[]).await
     }
 
     /// Fetches product options and flattens variants
-    pub async fn fetch_and_flatten_variants(&self) -> Result<Vec<FlattenedProductVariant>> {
+    pub async fn fetch_and_flatten_variants(&self) -> Result<Vec<FlattenedProductVariant>, PureApiError> {
         info!("Fetching product options from Pure API");
 
         let product_options = with_retry_and_rate_limit(
             || self.fetch_product_options_raw(),
             &self.retry_config,
+            &self.rate_limiter,
+            &self.circuit_breaker,
             "Fetch product options"
         ).await?;
 
@@ -161,68 +242,82 @@ impl PureApiClient {
     }
 
     /// Extracts unique product IDs from variants
+    ///
+    /// Sorted, so the same catalog is always fetched in the same batches and a
+    /// recorded cassette can be replayed.
     pub fn deduplicate_product_ids(variants: &[FlattenedProductVariant]) -> Vec<String> {
         variants
             .iter()
             .map(|v| v.pure_product_id.clone())
-            .collect::<HashSet<_>>()
+            .collect::<BTreeSet<_>>()
             .into_iter()
             .collect()
     }
 
     /// Fetches a batch of products from the API
-    async fn fetch_products_batch_raw(&self, product_ids: &[String]) -> Result<Vec<ProductData>> {
+    async fn fetch_products_batch_raw(&self, product_ids: &[String]) -> Result<Vec<ProductData>, PureApiError> {
         let ids_param = product_ids.join(",");
-        let url = format!("{}{}", self.api_base_url, GET_PRODUCTS_PATH);
-
-        let response = self.client
-            .get(&url)
-            .header("x-api-key", &self.api_key)
-            .query(&[("ids", ids_param)])
-            .send()
-            .await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!("API request failed with status {}: {}", status, body));
-        }
-
-        let products_response: ProductsResponse = response.json().await?;
+        let products_response: ProductsResponse = self.get_json(GET_PRODUCTS_PATH, &[("ids", &ids_param)]).await?;
         Ok(products_response.data)
     }
 
     /// Fetches products in batches with progress logging
-    pub async fn fetch_products_in_batches(&self, product_ids: &[String]) -> Result<Vec<ProductData>> {
-        info!("Fetching {} products in batches of {}", product_ids.len(), self.product_batch_size);
+    ///
+    /// Up to `product_batch_concurrency` batches are in flight at once, all
+    /// sharing the client's rate limiter.
+    pub async fn fetch_products_in_batches(
+        &self,
+        product_ids: &[String],
+    ) -> Result<(Vec<ProductData>, Vec<FailedBatch>)> {
+        info!(
+            "Fetching {} products in batches of {}, {} at a time",
+            product_ids.len(), self.product_batch_size, self.product_batch_concurrency
+        );
 
         let mut all_products = Vec::new();
-        let total_batches = (product_ids.len() + self.product_batch_size - 1) / self.product_batch_size;
-
-        for (batch_index, chunk) in product_ids.chunks(self.product_batch_size).enumerate() {
-            let batch_num = batch_index + 1;
-            let context = format!("Fetch products batch {}/{}", batch_num, total_batches);
-
-            info!("[{}/{}] Fetching batch of {} products", batch_num, total_batches, chunk.len());
+        let mut failed_batches = Vec::new();
+        let total_batches = product_ids.len().div_ceil(self.product_batch_size);
+
+        let mut batches = stream::iter(product_ids.chunks(self.product_batch_size).enumerate())
+            .map(|(batch_index, chunk)| async move {
+                let batch_num = batch_index + 1;
+                let context = format!("Fetch products batch {}/{}", batch_num, total_batches);
+
+                info!("[{}/{}] Fetching batch of {} products", batch_num, total_batches, chunk.len());
+
+                let result = with_retry_and_rate_limit(
+                    || self.fetch_products_batch_raw(chunk),
+                    &self.retry_config,
+                    &self.rate_limiter,
+                    &self.circuit_breaker,
+                    &context
+                ).await;
+                (batch_num, chunk, result)
+            })
+            .buffer_unordered(self.product_batch_concurrency.max(1));
 
-            match with_retry_and_rate_limit(
-                || self.fetch_products_batch_raw(chunk),
-                &self.retry_config,
-                &context
-            ).await {
+        while let Some((batch_num, chunk, result)) = batches.next().await {
+            match result {
                 Ok(products) => {
                     info!("[{}/{}] Successfully fetched {} products", batch_num, total_batches, products.len());
                     all_products.extend(products);
                 }
+                // The API is down, so the rest of the batches would fail too
+                Err(e @ PureApiError::CircuitOpen(_)) => return Err(e.into()),
                 Err(e) => {
                     info!("[{}/{}] Failed to fetch batch: {}", batch_num, total_batches, e);
                     // Continue with other batches instead of failing completely
+                    failed_batches.push(FailedBatch {
+                        product_ids: chunk.to_vec(),
+                        error_kind: e.kind(),
+                        error: e.to_string(),
+                    });
                 }
             }
         }
 
         info!("Successfully fetched {} out of {} products", all_products.len(), product_ids.len());
-        Ok(all_products)
+        Ok((all_products, failed_batches))
     }
 
     /// Builds a HashMap of product ID to ProductData
@@ -271,7 +366,7 @@ impl PureApiClient {
                         pure_variant_id: variant.pure_variant_id,
                         name: product.title.clone(),
                         sku: product.sku.clone(),
-                        material: product.material.clone(),
+                        material: Material::from_raw(&product.material),
                         variant_label: variant.pure_variant_label,
                         image_url,
                         highest_offer_spot_premium: highest_offer_premium,
@@ -284,18 +379,28 @@ impl PureApiClient {
     }
 
     /// Orchestrates the full product building pipeline
-    pub async fn build_new_products(&self) -> Result<Vec<NewProduct>> {
+    pub async fn build_new_products(&self) -> Result<ProductCatalog> {
         let variants = self.fetch_and_flatten_variants().await?;
 
         let product_ids = Self::deduplicate_product_ids(&variants);
         info!("Found {} unique products to fetch", product_ids.len());
 
-        let products = self.fetch_products_in_batches(&product_ids).await?;
+        let listed_variants = variants
+            .iter()
+            .map(|v| (v.pure_product_id.clone(), v.pure_variant_id.clone()))
+            .collect();
+
+        let (products, failed_batches) = self.fetch_products_in_batches(&product_ids).await?;
         let product_map = Self::build_product_map(products);
         let new_products = Self::combine_variants_with_products(variants, &product_map);
         info!("Built {} NewProduct models", new_products.len());
 
-        Ok(new_products)
+        Ok(ProductCatalog {
+            products: new_products,
+            products_attempted: product_ids.len(),
+            failed_batches,
+            listed_variants,
+        })
     }
 
     /// Fetches product activity for a specific product/variant
@@ -303,22 +408,9 @@ impl PureApiClient {
         &self,
         product_id: &str,
         variant_id: &str,
-    ) -> Result<Vec<ActivityEvent>> {
-        let url = format!("{}{}", self.api_base_url, PRODUCT_ACTIVITY_PATH);
-        let response = self.client
-            .get(&url)
-            .header("x-api-key", &self.api_key)
-            .query(&[("productId", product_id), ("variantId", variant_id)])
-            .send()
-            .await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!("API request failed with status {}: {}", status, body));
-        }
-
-        let activity_response: ProductActivityResponse = response.json().await?;
+    ) -> Result<Vec<ActivityEvent>, PureApiError> {
+        let query = [("productId", product_id), ("variantId", variant_id)];
+        let activity_response: ProductActivityResponse = self.get_json(PRODUCT_ACTIVITY_PATH, &query).await?;
         Ok(activity_response.data)
     }
 
@@ -327,12 +419,14 @@ impl PureApiClient {
         &self,
         product_id: &str,
         variant_id: &str,
-    ) -> Result<Vec<ActivityEvent>> {
+    ) -> Result<Vec<ActivityEvent>, PureApiError> {
         let context = format!("Fetch activity for product: {}, variant: {}", product_id, variant_id);
 
         with_retry_and_rate_limit(
             || self.fetch_product_activity_raw(product_id, variant_id),
             &self.retry_config,
+            &self.rate_limiter,
+            &self.circuit_breaker,
             &context
         ).await
     }
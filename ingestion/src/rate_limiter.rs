@@ -0,0 +1,220 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// Token bucket shared by every request made through one `PureApiClient`
+///
+/// The bucket holds up to `burst` tokens and refills at `requests_per_minute`.
+/// Each request takes a token; when none are left it reserves the next one and
+/// waits for it, so concurrent callers are served in the order they asked.
+#[derive(Debug)]
+pub struct RateLimiter {
+    tokens_per_sec: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+    stats: Stats,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available; negative when callers are queued for future tokens
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    acquired: AtomicU64,
+    waited: AtomicU64,
+    wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// Cumulative time spent waiting for tokens since the limiter was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimiterStats {
+    /// Tokens handed out
+    pub acquired: u64,
+    /// Of those, how many had to wait
+    pub waited: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl RateLimiterStats {
+    pub fn average_wait(&self) -> Duration {
+        if self.acquired == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait / self.acquired as u32
+    }
+}
+
+impl RateLimiter {
+    /// A limiter allowing `requests_per_minute` on average and bursts of up to `burst`
+    ///
+    /// A rate of zero disables limiting.
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            tokens_per_sec: f64::from(requests_per_minute) / 60.0,
+            burst,
+            bucket: Mutex::new(Bucket { tokens: burst, refilled_at: Instant::now() }),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        self.record(wait);
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+
+    /// Holds every request back until `until`, for when the server has asked
+    /// us to slow down
+    ///
+    /// The bucket doesn't refill while paused, so only one request may go as
+    /// soon as the pause ends and the rest follow at the usual rate.
+    pub fn pause_until(&self, until: Instant) {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if until <= bucket.refilled_at {
+            return;
+        }
+
+        self.refill(&mut bucket, Instant::now());
+        bucket.tokens = bucket.tokens.min(1.0);
+        bucket.refilled_at = until;
+    }
+
+    /// Takes a token at `now`, returning how long to wait before it is usable
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+
+        // A pause holds requests back even when limiting is disabled
+        let paused = bucket.refilled_at.saturating_duration_since(now);
+        if self.tokens_per_sec <= 0.0 {
+            return paused;
+        }
+
+        self.refill(&mut bucket, now);
+
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            paused
+        } else {
+            paused + Duration::from_secs_f64(-bucket.tokens / self.tokens_per_sec)
+        }
+    }
+
+    /// Adds the tokens earned since the bucket was last refilled, unless it is paused past `now`
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.tokens_per_sec).min(self.burst);
+        bucket.refilled_at = bucket.refilled_at.max(now);
+    }
+
+    fn record(&self, wait: Duration) {
+        let micros = wait.as_micros() as u64;
+        self.stats.acquired.fetch_add(1, Ordering::Relaxed);
+        if micros > 0 {
+            self.stats.waited.fetch_add(1, Ordering::Relaxed);
+            self.stats.wait_micros.fetch_add(micros, Ordering::Relaxed);
+            self.stats.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> RateLimiterStats {
+        RateLimiterStats {
+            acquired: self.stats.acquired.load(Ordering::Relaxed),
+            waited: self.stats.waited.load(Ordering::Relaxed),
+            total_wait: Duration::from_micros(self.stats.wait_micros.load(Ordering::Relaxed)),
+            max_wait: Duration::from_micros(self.stats.max_wait_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_is_free_then_requests_are_spaced() {
+        // 60 per minute is one token a second
+        let limiter = RateLimiter::new(60, 3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(start), Duration::ZERO);
+        }
+        // Each caller beyond the burst queues one more second behind the last
+        assert_eq!(limiter.reserve(start), Duration::from_secs(1));
+        assert_eq!(limiter.reserve(start), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_tokens_refill_up_to_burst() {
+        let limiter = RateLimiter::new(60, 2);
+        let start = Instant::now();
+
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+
+        // A long idle period only refills the bucket to its burst size
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert_eq!(limiter.reserve(later), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_pause_holds_requests_until_it_ends() {
+        let limiter = RateLimiter::new(60, 3);
+        let start = Instant::now();
+        limiter.pause_until(start + Duration::from_secs(10));
+
+        // Only one request goes when the pause ends, however full the bucket was
+        assert_eq!(limiter.reserve(start), Duration::from_secs(10));
+        assert_eq!(limiter.reserve(start), Duration::from_secs(11));
+
+        // Tokens refill as usual once it has ended
+        let later = start + Duration::from_secs(20);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_pause_applies_with_limiting_disabled() {
+        let limiter = RateLimiter::new(0, 1);
+        let start = Instant::now();
+        limiter.pause_until(start + Duration::from_secs(5));
+        assert_eq!(limiter.reserve(start), Duration::from_secs(5));
+        assert_eq!(limiter.reserve(start + Duration::from_secs(5)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_zero_rate_disables_limiting() {
+        let limiter = RateLimiter::new(0, 1);
+        let start = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(limiter.reserve(start), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_stats_track_waits() {
+        let limiter = RateLimiter::new(60, 1);
+        limiter.record(Duration::ZERO);
+        limiter.record(Duration::from_secs(2));
+        limiter.record(Duration::from_secs(1));
+
+        let stats = limiter.stats();
+        assert_eq!(stats.acquired, 3);
+        assert_eq!(stats.waited, 2);
+        assert_eq!(stats.total_wait, Duration::from_secs(3));
+        assert_eq!(stats.max_wait, Duration::from_secs(2));
+        assert_eq!(stats.average_wait(), Duration::from_secs(1));
+    }
+}
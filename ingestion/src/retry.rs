@@ -1,67 +1,180 @@
-use anyhow::Result;
+use rand::Rng;
 use std::future::Future;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 use tracing::{info, error};
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::error::PureApiError;
+use crate::rate_limiter::RateLimiter;
+
 pub struct RetryConfig {
     pub max_retries: u32,
     pub initial_backoff: Duration,
-    pub rate_limit_delay: Duration,
+    /// Upper bound on the exponential backoff between attempts
+    pub max_backoff: Duration,
+    /// Longest `Retry-After` we will honour; the server may ask for more than
+    /// `max_backoff`, but a request asking for longer than this gives up
+    pub max_retry_after: Duration,
+}
+
+impl RetryConfig {
+    /// Exponential backoff before retry number `retry` (1-based), capped at
+    /// `max_backoff`, with `jitter` (0 to 1) spreading it over its upper half
+    fn backoff(&self, retry: u32, jitter: f64) -> Duration {
+        let exponential = self.initial_backoff.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let capped = exponential.min(self.max_backoff);
+        capped.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+
+    /// How long to wait before retry number `retry` after `error`, or `None`
+    /// if the server asked for a longer wait than `max_retry_after`
+    fn delay_before_retry(&self, retry: u32, error: &PureApiError, jitter: f64) -> Option<Duration> {
+        match error.retry_after() {
+            Some(retry_after) if retry_after > self.max_retry_after => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff(retry, jitter)),
+        }
+    }
 }
 
 /// Executes an async operation with rate limiting and exponential backoff retry logic.
 ///
-/// - Enforces a rate limit delay before each attempt
-/// - Retries on all errors up to max_retries
-/// - Uses exponential backoff starting from initial_backoff
+/// - Takes a token from the rate limiter before each attempt, retries included
+/// - Fails fast without sending anything while the circuit breaker is open, and
+///   reports each attempt's outcome to it; 429s count neither way
+/// - Retries only errors that may succeed on another attempt (transport errors,
+///   429s and 5xx), up to max_retries; anything else fails immediately
+/// - Waits as long as a `Retry-After` header asks, up to max_retry_after,
+///   otherwise backs off exponentially from initial_backoff with jitter,
+///   capped at max_backoff
+/// - Pauses the rate limiter for the whole wait after a 429, so concurrent
+///   requests hold off too
 ///
 /// # Arguments
-/// * `operation` - A closure that returns a future producing a Result<T>
-/// * `config` - Retry configuration (max retries, backoff, rate limit delay)
+/// * `operation` - A closure that returns a future producing a Result<T, PureApiError>
+/// * `config` - Retry configuration (max retries, backoff bounds)
+/// * `rate_limiter` - Limiter shared by every request to the same API
+/// * `circuit_breaker` - Breaker shared by every request to the same API
 /// * `context` - Description of the operation for logging
 pub async fn with_retry_and_rate_limit<F, Fut, T>(
     mut operation: F,
     config: &RetryConfig,
+    rate_limiter: &RateLimiter,
+    circuit_breaker: &CircuitBreaker,
     context: &str,
-) -> Result<T>
+) -> Result<T, PureApiError>
 where
     F: FnMut() -> Fut,
-    Fut: Future<Output = Result<T>>,
+    Fut: Future<Output = Result<T, PureApiError>>,
 {
     let mut retry_count = 0;
-    let mut backoff = config.initial_backoff;
 
     loop {
-        // Enforce rate limiting before each attempt
-        if retry_count > 0 {
-            info!("{} - Waiting {} seconds before retry attempt {}/{}",
-                  context, backoff.as_secs(), retry_count, config.max_retries);
-            sleep(backoff).await;
-        } else {
-            // Always apply rate limit delay, even on first attempt
-            sleep(config.rate_limit_delay).await;
-        }
+        let permit = circuit_breaker.acquire().await.map_err(PureApiError::CircuitOpen)?;
 
-        match operation().await {
+        // Every attempt counts against the API quota
+        rate_limiter.acquire().await;
+
+        let e = match operation().await {
             Ok(result) => {
+                permit.record_success();
                 if retry_count > 0 {
                     info!("{} - Success after {} retries", context, retry_count);
                 }
                 return Ok(result);
             }
             Err(e) => {
-                if retry_count < config.max_retries {
-                    error!("{} - Attempt {}/{} failed: {}. Retrying with exponential backoff...",
-                           context, retry_count + 1, config.max_retries + 1, e);
-                    retry_count += 1;
-                    backoff *= 2; // Exponential backoff
-                    continue;
-                } else {
-                    error!("{} - Failed after {} retries: {}", context, config.max_retries, e);
-                    return Err(e);
+                match &e {
+                    // Being told to slow down isn't a sign the API is failing
+                    PureApiError::RateLimited { .. } => permit.record_neutral(),
+                    e if e.is_retryable() => permit.record_failure(&e.to_string()),
+                    _ => permit.record_success(),
                 }
+                e
             }
+        };
+
+        if !e.is_retryable() {
+            error!("{} - Failed with non-retryable {} error: {}", context, e.kind(), e);
+            return Err(e);
+        }
+
+        if retry_count >= config.max_retries {
+            error!("{} - Failed after {} retries: {}", context, config.max_retries, e);
+            return Err(e);
+        }
+
+        retry_count += 1;
+        let jitter = rand::thread_rng().gen_range(0.0..=1.0);
+        let Some(delay) = config.delay_before_retry(retry_count, &e, jitter) else {
+            error!(
+                "{} - Server asked to retry after {:?}, longer than the {:?} we honour: {}",
+                context, e.retry_after(), config.max_retry_after, e
+            );
+            return Err(e);
+        };
+
+        if matches!(e, PureApiError::RateLimited { .. }) {
+            rate_limiter.pause_until(Instant::now() + delay);
         }
+
+        error!("{} - Attempt {}/{} failed: {}. Retrying in {:.1}s...",
+               context, retry_count, config.max_retries + 1, e, delay.as_secs_f64());
+        sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 10,
+            initial_backoff: Duration::from_secs(6),
+            max_backoff: Duration::from_secs(60),
+            max_retry_after: Duration::from_secs(900),
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let config = config();
+        assert_eq!(config.backoff(1, 1.0), Duration::from_secs(6));
+        assert_eq!(config.backoff(2, 1.0), Duration::from_secs(12));
+        assert_eq!(config.backoff(4, 1.0), Duration::from_secs(48));
+        assert_eq!(config.backoff(5, 1.0), Duration::from_secs(60));
+        assert_eq!(config.backoff(30, 1.0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_backoff_jitter_spans_upper_half() {
+        let config = config();
+        assert_eq!(config.backoff(2, 0.0), Duration::from_secs(6));
+        assert_eq!(config.backoff(2, 0.5), Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_retry_after_overrides_backoff() {
+        let config = config();
+        let rate_limited = |seconds| PureApiError::RateLimited {
+            retry_after: Some(Duration::from_secs(seconds)),
+            body: String::new(),
+        };
+
+        assert_eq!(config.delay_before_retry(1, &rate_limited(30), 1.0), Some(Duration::from_secs(30)));
+        // Longer than any backoff, but still honoured
+        assert_eq!(config.delay_before_retry(1, &rate_limited(300), 1.0), Some(Duration::from_secs(300)));
+        // Longer than we're willing to wait
+        assert_eq!(config.delay_before_retry(1, &rate_limited(3600), 1.0), None);
+
+        let server = PureApiError::Server {
+            status: StatusCode::BAD_GATEWAY,
+            retry_after: None,
+            body: String::new(),
+        };
+        assert_eq!(config.delay_before_retry(3, &server, 1.0), Some(Duration::from_secs(24)));
     }
 }
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::Product;
+use sqlx::{FromRow, PgPool};
+
+use crate::config::Config;
+
+/// A variant whose activity is due to be polled
+#[derive(Debug, Clone, FromRow)]
+pub struct DueVariant {
+    #[sqlx(flatten)]
+    pub product: Product,
+    /// Latest `event_time` already stored for the variant
+    pub watermark: Option<DateTime<Utc>>,
+}
+
+/// How busy a variant has been over the scheduler's activity window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromRow)]
+pub struct VariantActivity {
+    pub trade_count: i64,
+    /// Market snapshots whose bid or ask differed from the one before
+    pub market_changes: i64,
+}
+
+/// When a variant is next polled, and the interval that was chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollSchedule {
+    pub next_poll_at: DateTime<Utc>,
+    pub interval: TimeDelta,
+}
+
+/// Decides which variants to poll for activity, and how often
+///
+/// Each variant is polled roughly once per trade or market data change it has
+/// seen over the activity window, clamped between the minimum and maximum poll
+/// intervals. A market data change since the last poll makes a variant due
+/// immediately. Each sync polls at most `budget` variants, most overdue first.
+/// Delisted variants are polled for `delisted_grace` after they were delisted,
+/// to pick up their last trades, and then no more.
+#[derive(Debug, Clone)]
+pub struct PollScheduler {
+    min_interval: TimeDelta,
+    max_interval: TimeDelta,
+    activity_window: TimeDelta,
+    delisted_grace: TimeDelta,
+    budget: usize,
+}
+
+impl PollScheduler {
+    pub fn new(
+        min_interval: TimeDelta,
+        max_interval: TimeDelta,
+        activity_window: TimeDelta,
+        delisted_grace: TimeDelta,
+        budget: usize,
+    ) -> Self {
+        Self {
+            min_interval,
+            max_interval: max_interval.max(min_interval),
+            activity_window,
+            delisted_grace,
+            budget,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self::new(
+            TimeDelta::from_std(config.min_poll_interval)?,
+            TimeDelta::from_std(config.max_poll_interval)?,
+            TimeDelta::from_std(config.activity_window)?,
+            TimeDelta::from_std(config.delisted_poll_grace)?,
+            config.polls_per_transaction_sync(),
+        ))
+    }
+
+    /// Time between polls for a variant with the given recent activity
+    pub fn poll_interval(&self, activity: VariantActivity) -> TimeDelta {
+        let events = (activity.trade_count + activity.market_changes) as f64;
+        if events <= 0.0 {
+            return self.max_interval;
+        }
+
+        let seconds = self.activity_window.num_seconds() as f64 / events;
+        TimeDelta::seconds(seconds as i64).clamp(self.min_interval, self.max_interval)
+    }
+
+    /// Variants due for a poll at `now`, most overdue first, up to the budget
+    ///
+    /// Variants that have never been polled are always due. Variants delisted
+    /// for longer than the grace period never are.
+    pub async fn due_variants(&self, pool: &PgPool, now: DateTime<Utc>) -> Result<Vec<DueVariant>> {
+        let limit = i64::try_from(self.budget).unwrap_or(i64::MAX);
+
+        let variants = sqlx::query_as::<_, DueVariant>(
+            r#"
+            SELECT p.*, s.watermark
+            FROM products p
+            LEFT JOIN variant_sync_state s
+                ON s.pure_product_id = p.pure_product_id
+                AND s.pure_variant_id = p.pure_variant_id
+            CROSS JOIN LATERAL (
+                SELECT LEAST(
+                    COALESCE(s.next_poll_at, '-infinity'::TIMESTAMPTZ),
+                    CASE WHEN p.market_data_updated_at > s.market_data_seen_at THEN p.market_data_updated_at END
+                ) as due_at
+            ) d
+            WHERE d.due_at <= $1
+                AND (p.delisted_at IS NULL OR p.delisted_at > $3)
+            ORDER BY d.due_at, p.id
+            LIMIT $2
+            "#
+        )
+        .bind(now)
+        .bind(limit)
+        .bind(now - self.delisted_grace)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(variants)
+    }
+
+    /// Counts a variant's trades and market data changes over the activity window
+    pub async fn fetch_activity(&self, pool: &PgPool, product: &Product, now: DateTime<Utc>) -> Result<VariantActivity> {
+        let activity = sqlx::query_as::<_, VariantActivity>(
+            r#"
+            SELECT
+                (
+                    SELECT COUNT(*)
+                    FROM transactions t
+                    WHERE t.pure_product_id = $1
+                        AND t.pure_variant_id = $2
+                        AND t.event_time >= $3
+                ) as trade_count,
+                (
+                    SELECT COUNT(*)
+                    FROM (
+                        SELECT
+                            ROW_NUMBER() OVER w as n,
+                            (highest_offer_spot_premium, lowest_listing_spot_premium)
+                                IS DISTINCT FROM
+                            (LAG(highest_offer_spot_premium) OVER w, LAG(lowest_listing_spot_premium) OVER w) as changed
+                        FROM market_snapshots ms
+                        WHERE ms.pure_product_id = $1
+                            AND ms.pure_variant_id = $2
+                            AND ms.captured_at >= $3
+                        WINDOW w AS (ORDER BY ms.captured_at)
+                    ) c
+                    WHERE c.n > 1 AND c.changed
+                ) as market_changes
+            "#
+        )
+        .bind(&product.pure_product_id)
+        .bind(&product.pure_variant_id)
+        .bind(now - self.activity_window)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(activity)
+    }
+
+    /// Schedules the next poll of a variant from its recent activity
+    pub async fn schedule_next_poll(&self, pool: &PgPool, product: &Product, now: DateTime<Utc>) -> Result<PollSchedule> {
+        let activity = self.fetch_activity(pool, product, now).await?;
+        let interval = self.poll_interval(activity);
+        Ok(PollSchedule { next_poll_at: now + interval, interval })
+    }
+
+    /// Pushes a variant whose poll failed back by the minimum interval, so a
+    /// persistently failing variant doesn't use up every sync's budget
+    pub async fn defer_failed_poll(&self, pool: &PgPool, product: &Product, now: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO variant_sync_state (pure_product_id, pure_variant_id, product_id, next_poll_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (pure_product_id, pure_variant_id)
+            DO UPDATE SET next_poll_at = EXCLUDED.next_poll_at
+            "#
+        )
+        .bind(&product.pure_product_id)
+        .bind(&product.pure_variant_id)
+        .bind(product.id)
+        .bind(now + self.min_interval)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> PollScheduler {
+        PollScheduler::new(TimeDelta::minutes(15), TimeDelta::hours(24), TimeDelta::days(7), TimeDelta::days(1), 100)
+    }
+
+    #[test]
+    fn test_dormant_variant_polled_at_max_interval() {
+        assert_eq!(scheduler().poll_interval(VariantActivity::default()), TimeDelta::hours(24));
+
+        // Fewer than one event a day is still clamped to the maximum
+        let activity = VariantActivity { trade_count: 3, market_changes: 0 };
+        assert_eq!(scheduler().poll_interval(activity), TimeDelta::hours(24));
+    }
+
+    #[test]
+    fn test_active_variant_polled_once_per_event() {
+        // 14 trades and 14 market changes a week is one event every 6 hours
+        let activity = VariantActivity { trade_count: 14, market_changes: 14 };
+        assert_eq!(scheduler().poll_interval(activity), TimeDelta::hours(6));
+    }
+
+    #[test]
+    fn test_hot_variant_clamped_to_min_interval() {
+        let activity = VariantActivity { trade_count: 5000, market_changes: 200 };
+        assert_eq!(scheduler().poll_interval(activity), TimeDelta::minutes(15));
+    }
+}
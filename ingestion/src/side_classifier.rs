@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Result};
+use common::EventType;
+
+/// Confidence given to a side inferred only from the direction of the last trade
+const TICK_RULE_CONFIDENCE: f64 = 0.5;
+
+/// Inferred trade side with a confidence between 0 (a coin flip) and 1 (certain)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SideEstimate {
+    pub side: EventType,
+    pub confidence: f64,
+}
+
+impl SideEstimate {
+    fn unknown() -> Self {
+        Self { side: EventType::Unknown, confidence: 0.0 }
+    }
+}
+
+/// Market quotes and trade history a side is inferred from
+#[derive(Debug, Clone, Copy)]
+pub struct TradeQuote {
+    pub premium: f64,
+    pub highest_offer: f64,
+    pub lowest_listing: f64,
+    /// Premium of the previous trade in the same variant, if any
+    pub previous_premium: Option<f64>,
+}
+
+impl TradeQuote {
+    fn midpoint(&self) -> f64 {
+        (self.highest_offer + self.lowest_listing) / 2.0
+    }
+
+    /// Side of the quote the trade is nearest to, ties going to sell
+    fn nearest_side(&self) -> EventType {
+        let dist_to_offer = (self.premium - self.highest_offer).abs();
+        let dist_to_listing = (self.premium - self.lowest_listing).abs();
+        if dist_to_listing < dist_to_offer { EventType::Buy } else { EventType::Sell }
+    }
+
+    /// How far the trade sits from the midpoint, as a fraction of the half spread
+    ///
+    /// 0 at the midpoint, 1 on (or beyond) either quote.
+    fn distance_from_midpoint(&self) -> f64 {
+        let half_spread = (self.lowest_listing - self.highest_offer).abs() / 2.0;
+        let distance = (self.premium - self.midpoint()).abs();
+        if half_spread <= f64::EPSILON {
+            return if distance <= f64::EPSILON { 0.0 } else { 1.0 };
+        }
+        (distance / half_spread).min(1.0)
+    }
+
+    fn is_at_midpoint(&self) -> bool {
+        (self.premium - self.midpoint()).abs() <= f64::EPSILON
+    }
+
+    /// Tick test: an uptick from the previous trade is a buy, a downtick a sell
+    fn tick_side(&self) -> Option<EventType> {
+        let previous = self.previous_premium?;
+        if self.premium > previous {
+            Some(EventType::Buy)
+        } else if self.premium < previous {
+            Some(EventType::Sell)
+        } else {
+            None
+        }
+    }
+}
+
+/// Strategy for inferring which side initiated a trade
+pub trait SideClassifier: Send + Sync {
+    /// Name used to select the strategy in configuration
+    fn name(&self) -> &'static str;
+
+    fn classify(&self, quote: &TradeQuote) -> SideEstimate;
+}
+
+/// Assigns the side of whichever quote the trade is closest to
+///
+/// Confidence grows linearly from 0 at the midpoint to 1 at either quote.
+pub struct NearestQuote;
+
+impl SideClassifier for NearestQuote {
+    fn name(&self) -> &'static str {
+        "nearest_quote"
+    }
+
+    fn classify(&self, quote: &TradeQuote) -> SideEstimate {
+        SideEstimate {
+            side: quote.nearest_side(),
+            confidence: quote.distance_from_midpoint(),
+        }
+    }
+}
+
+/// Midpoint rule, falling back to the tick test for trades exactly at the midpoint
+pub struct MidpointTickRule;
+
+impl SideClassifier for MidpointTickRule {
+    fn name(&self) -> &'static str {
+        "midpoint_tick"
+    }
+
+    fn classify(&self, quote: &TradeQuote) -> SideEstimate {
+        if !quote.is_at_midpoint() {
+            return NearestQuote.classify(quote);
+        }
+
+        match quote.tick_side() {
+            Some(side) => SideEstimate { side, confidence: TICK_RULE_CONFIDENCE },
+            None => SideEstimate::unknown(),
+        }
+    }
+}
+
+/// Lee–Ready style: quote rule, with the tick test breaking midpoint ties and
+/// corroborating trades inside the spread
+///
+/// A tick test that agrees with the quote rule raises confidence halfway to 1.
+pub struct LeeReady;
+
+impl SideClassifier for LeeReady {
+    fn name(&self) -> &'static str {
+        "lee_ready"
+    }
+
+    fn classify(&self, quote: &TradeQuote) -> SideEstimate {
+        let estimate = MidpointTickRule.classify(quote);
+        if quote.is_at_midpoint() {
+            return estimate;
+        }
+
+        match quote.tick_side() {
+            Some(side) if side == estimate.side => SideEstimate {
+                side,
+                confidence: (1.0 + estimate.confidence) / 2.0,
+            },
+            _ => estimate,
+        }
+    }
+}
+
+/// Looks up a classifier by the name it is configured with
+pub fn from_name(name: &str) -> Result<Box<dyn SideClassifier>> {
+    match name {
+        "nearest_quote" => Ok(Box::new(NearestQuote)),
+        "midpoint_tick" => Ok(Box::new(MidpointTickRule)),
+        "lee_ready" => Ok(Box::new(LeeReady)),
+        other => Err(anyhow!("Unknown side classifier: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(premium: f64, previous_premium: Option<f64>) -> TradeQuote {
+        TradeQuote {
+            premium,
+            highest_offer: 2.0,
+            lowest_listing: 6.0,
+            previous_premium,
+        }
+    }
+
+    #[test]
+    fn test_nearest_quote_on_the_bid_is_certain_sell() {
+        let estimate = NearestQuote.classify(&quote(2.0, None));
+        assert_eq!(estimate, SideEstimate { side: EventType::Sell, confidence: 1.0 });
+    }
+
+    #[test]
+    fn test_nearest_quote_at_midpoint_has_no_confidence() {
+        let estimate = NearestQuote.classify(&quote(4.0, None));
+        assert_eq!(estimate.side, EventType::Sell);
+        assert_eq!(estimate.confidence, 0.0);
+    }
+
+    #[test]
+    fn test_nearest_quote_inside_spread_is_partial() {
+        let estimate = NearestQuote.classify(&quote(5.0, None));
+        assert_eq!(estimate, SideEstimate { side: EventType::Buy, confidence: 0.5 });
+    }
+
+    #[test]
+    fn test_midpoint_tick_uses_tick_at_midpoint() {
+        let estimate = MidpointTickRule.classify(&quote(4.0, Some(3.0)));
+        assert_eq!(estimate, SideEstimate { side: EventType::Buy, confidence: TICK_RULE_CONFIDENCE });
+
+        let estimate = MidpointTickRule.classify(&quote(4.0, Some(4.0)));
+        assert_eq!(estimate, SideEstimate::unknown());
+    }
+
+    #[test]
+    fn test_lee_ready_corroborated_by_tick() {
+        let estimate = LeeReady.classify(&quote(5.0, Some(4.5)));
+        assert_eq!(estimate, SideEstimate { side: EventType::Buy, confidence: 0.75 });
+
+        // A downtick disagrees with the quote rule, so confidence is not raised
+        let estimate = LeeReady.classify(&quote(5.0, Some(5.5)));
+        assert_eq!(estimate, SideEstimate { side: EventType::Buy, confidence: 0.5 });
+    }
+
+    #[test]
+    fn test_from_name_rejects_unknown_strategy() {
+        assert_eq!(from_name("lee_ready").unwrap().name(), "lee_ready");
+        assert!(from_name("coin_flip").is_err());
+    }
+}
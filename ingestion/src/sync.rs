@@ -0,0 +1,585 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{MarketSnapshot, NewProduct, NewTransaction, Product, SyncRunKind};
+use futures::stream::{self, StreamExt};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{info, error, warn};
+
+use crate::error::PureApiError;
+use crate::event_type::{self, TradeClassifier};
+use crate::listings::record_listings;
+use crate::market_snapshots::{fetch_snapshots_between, insert_market_snapshots};
+use crate::product_history::record_product_history;
+use crate::pure_api::{ActivityEvent, PureApiClient};
+use crate::scheduler::{DueVariant, PollScheduler};
+use crate::sync_runs::{SyncRun, UpsertCounts};
+use crate::watermarks::record_variant_sync;
+
+/// Parses the timestamp format used by the Pure activity endpoint
+fn parse_event_time(created_at: &str) -> Result<DateTime<Utc>> {
+    let event_time = DateTime::parse_from_str(created_at, "%Y-%m-%d %H:%M:%S%.f%#z")?;
+    Ok(event_time.with_timezone(&Utc))
+}
+
+/// Parses an activity event into a transaction record
+fn parse_activity_to_transaction(
+    event: ActivityEvent,
+    product: &Product,
+    snapshots: &[MarketSnapshot],
+    previous_premium: Option<f64>,
+    classifier: &TradeClassifier,
+) -> Result<NewTransaction> {
+    let event_time = parse_event_time(&event.created_at)?;
+
+    // Trust the upstream side when given, otherwise classify against the
+    // market as it was when the trade happened
+    let classification = classifier.classify(
+        &event.event,
+        event.spot_premium,
+        event_time,
+        event_type::nearest_snapshot_before(snapshots, event_time),
+        previous_premium,
+    );
+
+    Ok(NewTransaction {
+        product_id: product.id,
+        pure_product_id: product.pure_product_id.clone(),
+        pure_variant_id: product.pure_variant_id.clone(),
+        price: event.price,
+        quantity: event.quantity,
+        spot_premium_percentage: event.spot_premium,
+        spot_premium_dollar: event.spot_premium_dollar,
+        event_time,
+        event_type: Some(classification.event_type),
+        event_type_confidence: Some(classification.confidence),
+        event_type_reason: classification.reason,
+        event_type_source: Some(classification.source),
+        upstream_event: Some(event.event),
+    })
+}
+
+/// Loads the market snapshots that could apply to any of the given events
+async fn fetch_snapshots_for_events(
+    pool: &PgPool,
+    product: &Product,
+    events: &[ActivityEvent],
+    classifier: &TradeClassifier,
+) -> Result<Vec<MarketSnapshot>> {
+    let event_times: Vec<DateTime<Utc>> = events
+        .iter()
+        .filter_map(|event| parse_event_time(&event.created_at).ok())
+        .collect();
+
+    let (Some(earliest), Some(latest)) = (event_times.iter().min(), event_times.iter().max()) else {
+        return Ok(Vec::new());
+    };
+
+    let max_age = TimeDelta::from_std(classifier.max_snapshot_age())?;
+
+    fetch_snapshots_between(
+        pool,
+        &product.pure_product_id,
+        &product.pure_variant_id,
+        *earliest - max_age,
+        *latest,
+    ).await
+}
+
+/// Fetches transactions for a single product newer than its watermark
+async fn fetch_transactions_for_product(
+    pool: &PgPool,
+    client: &PureApiClient,
+    product: &Product,
+    classifier: &TradeClassifier,
+    watermark: Option<DateTime<Utc>>,
+) -> Result<Vec<NewTransaction>> {
+    let mut events = client.fetch_product_activity(
+        &product.pure_product_id,
+        &product.pure_variant_id
+    ).await?;
+
+    // Oldest first, so each trade can be compared with the one before it
+    events.sort_by_key(|event| parse_event_time(&event.created_at).ok());
+
+    // Events at or before the watermark are already stored; the latest of them
+    // is still the previous trade for the first new one
+    let new_from = events.partition_point(|event| {
+        let event_time = parse_event_time(&event.created_at).ok();
+        watermark.is_some_and(|watermark| event_time.is_none_or(|time| time <= watermark))
+    });
+    let mut previous_premium = events[..new_from].last().map(|event| event.spot_premium);
+    let events = events.split_off(new_from);
+
+    let snapshots = fetch_snapshots_for_events(pool, product, &events, classifier).await?;
+
+    let mut transactions = Vec::new();
+    for event in events {
+        match parse_activity_to_transaction(event, product, &snapshots, previous_premium, classifier) {
+            Ok(transaction) => {
+                previous_premium = Some(transaction.spot_premium_percentage);
+                transactions.push(transaction);
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to parse transaction for product {}, variant {}: {}",
+                    product.pure_product_id, product.pure_variant_id, e
+                );
+            }
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Upserts products in chunks of `batch_size`, one statement per chunk
+///
+/// Rows whose content matches what is already stored are skipped rather than
+/// rewritten, and counted as unchanged. Changes to catalog fields are versioned
+/// in `product_history` in the same transaction.
+async fn upsert_products(
+    pool: &PgPool,
+    products: &[NewProduct],
+    batch_size: usize,
+) -> Result<UpsertCounts> {
+    let mut counts = UpsertCounts::default();
+    let mut revised = 0;
+
+    let products = dedup_last_by_key(products, |p| {
+        (p.pure_product_id.clone(), p.pure_variant_id.clone())
+    });
+    info!("Upserting {} products into database", products.len());
+
+    for chunk in products.chunks(batch_size.max(1)) {
+        let mut tx = pool.begin().await?;
+
+        // Skipped rows are not returned; `xmax = 0` only holds for inserted rows
+        let written: Vec<(i64, bool)> = sqlx::query_as(
+            r#"
+            INSERT INTO products (
+                pure_product_id,
+                pure_variant_id,
+                name,
+                sku,
+                material,
+                variant_label,
+                image_url,
+                highest_offer_spot_premium,
+                lowest_listing_spot_premium,
+                market_data_updated_at,
+                created_at,
+                updated_at
+            )
+            SELECT *, NOW(), NOW()
+            FROM UNNEST(
+                $1::VARCHAR[],
+                $2::VARCHAR[],
+                $3::VARCHAR[],
+                $4::VARCHAR[],
+                $5::material[],
+                $6::VARCHAR[],
+                $7::VARCHAR[],
+                $8::FLOAT8[],
+                $9::FLOAT8[],
+                $10::TIMESTAMPTZ[]
+            )
+            ON CONFLICT (pure_product_id, pure_variant_id)
+            DO UPDATE SET
+                name = EXCLUDED.name,
+                sku = EXCLUDED.sku,
+                material = EXCLUDED.material,
+                variant_label = EXCLUDED.variant_label,
+                image_url = EXCLUDED.image_url,
+                highest_offer_spot_premium = EXCLUDED.highest_offer_spot_premium,
+                lowest_listing_spot_premium = EXCLUDED.lowest_listing_spot_premium,
+                market_data_updated_at = EXCLUDED.market_data_updated_at,
+                updated_at = NOW()
+            WHERE (
+                products.name,
+                products.sku,
+                products.material,
+                products.variant_label,
+                products.image_url,
+                products.highest_offer_spot_premium,
+                products.lowest_listing_spot_premium
+            ) IS DISTINCT FROM (
+                EXCLUDED.name,
+                EXCLUDED.sku,
+                EXCLUDED.material,
+                EXCLUDED.variant_label,
+                EXCLUDED.image_url,
+                EXCLUDED.highest_offer_spot_premium,
+                EXCLUDED.lowest_listing_spot_premium
+            )
+            RETURNING id, (xmax = 0) AS inserted
+            "#
+        )
+        .bind(chunk.iter().map(|p| p.pure_product_id.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.pure_variant_id.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.name.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.sku.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.material).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.variant_label.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.image_url.as_deref()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.highest_offer_spot_premium).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.lowest_listing_spot_premium).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|p| p.market_data_updated_at).collect::<Vec<_>>())
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let written_ids: Vec<i64> = written.iter().map(|(id, _)| *id).collect();
+        revised += record_product_history(&mut tx, &written_ids).await?;
+        tx.commit().await?;
+
+        let chunk_inserted = written.iter().filter(|(_, was_inserted)| *was_inserted).count() as u64;
+        counts += UpsertCounts {
+            inserted: chunk_inserted,
+            updated: written.len() as u64 - chunk_inserted,
+            unchanged: (chunk.len() - written.len()) as u64,
+        };
+    }
+
+    info!(
+        "Successfully upserted products ({} inserted, {} updated, {} unchanged, {} with catalog changes)",
+        counts.inserted, counts.updated, counts.unchanged, revised
+    );
+
+    Ok(counts)
+}
+
+/// Drops items sharing a conflict key with a later item in the slice
+///
+/// A single `INSERT ... ON CONFLICT` statement cannot touch the same row twice,
+/// so only the last occurrence of each key is kept, matching row-by-row upserts.
+fn dedup_last_by_key<T, K, F>(items: &[T], key: F) -> Vec<&T>
+where
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let latest: HashMap<K, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (key(item), index))
+        .collect();
+
+    items
+        .iter()
+        .enumerate()
+        .filter(|(index, item)| latest[&key(item)] == *index)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Upserts transactions in chunks of `batch_size`, one statement per chunk
+async fn upsert_transactions_batch(
+    pool: &PgPool,
+    transactions: &[NewTransaction],
+    batch_size: usize,
+) -> Result<UpsertCounts> {
+    let mut counts = UpsertCounts::default();
+
+    if transactions.is_empty() {
+        return Ok(counts);
+    }
+
+    let transactions = dedup_last_by_key(transactions, |t| {
+        (t.event_time, t.pure_product_id.clone(), t.pure_variant_id.clone())
+    });
+    info!("Upserting {} transactions into database", transactions.len());
+
+    for chunk in transactions.chunks(batch_size.max(1)) {
+        // `xmax = 0` only holds for rows this statement inserted
+        let inserted: Vec<bool> = sqlx::query_scalar(
+            r#"
+            INSERT INTO transactions (
+                product_id,
+                pure_product_id,
+                pure_variant_id,
+                price,
+                quantity,
+                spot_premium_percentage,
+                spot_premium_dollar,
+                event_time,
+                event_type,
+                event_type_confidence,
+                event_type_reason,
+                event_type_source,
+                upstream_event,
+                created_at,
+                updated_at
+            )
+            SELECT *, NOW(), NOW()
+            FROM UNNEST(
+                $1::BIGINT[],
+                $2::VARCHAR[],
+                $3::VARCHAR[],
+                $4::BIGINT[],
+                $5::INTEGER[],
+                $6::FLOAT8[],
+                $7::BIGINT[],
+                $8::TIMESTAMPTZ[],
+                $9::event_type[],
+                $10::FLOAT8[],
+                $11::VARCHAR[],
+                $12::VARCHAR[],
+                $13::VARCHAR[]
+            )
+            ON CONFLICT (event_time, pure_product_id, pure_variant_id)
+            DO UPDATE SET
+                product_id = EXCLUDED.product_id,
+                price = EXCLUDED.price,
+                quantity = EXCLUDED.quantity,
+                spot_premium_percentage = EXCLUDED.spot_premium_percentage,
+                spot_premium_dollar = EXCLUDED.spot_premium_dollar,
+                event_type = EXCLUDED.event_type,
+                event_type_confidence = EXCLUDED.event_type_confidence,
+                event_type_reason = EXCLUDED.event_type_reason,
+                event_type_source = EXCLUDED.event_type_source,
+                upstream_event = EXCLUDED.upstream_event,
+                updated_at = NOW()
+            RETURNING (xmax = 0) AS inserted
+            "#
+        )
+        .bind(chunk.iter().map(|t| t.product_id).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.pure_product_id.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.pure_variant_id.as_str()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.price).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.quantity).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.spot_premium_percentage).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.spot_premium_dollar).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.event_time).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.event_type).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.event_type_confidence).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.event_type_reason.as_deref()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.event_type_source.as_deref()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|t| t.upstream_event.as_deref()).collect::<Vec<_>>())
+        .fetch_all(pool)
+        .await?;
+
+        let chunk_inserted = inserted.iter().filter(|&&was_inserted| was_inserted).count() as u64;
+        counts += UpsertCounts {
+            inserted: chunk_inserted,
+            updated: inserted.len() as u64 - chunk_inserted,
+            unchanged: 0,
+        };
+    }
+
+    info!(
+        "Successfully upserted {} transactions ({} inserted, {} updated)",
+        counts.written(), counts.inserted, counts.updated
+    );
+
+    Ok(counts)
+}
+
+/// Fetches one variant's new activity, stores it and schedules its next poll
+async fn sync_variant(
+    pool: &PgPool,
+    client: &PureApiClient,
+    classifier: &TradeClassifier,
+    scheduler: &PollScheduler,
+    variant: &DueVariant,
+    insert_batch_size: usize,
+) -> Result<UpsertCounts> {
+    let product = &variant.product;
+    let transactions = fetch_transactions_for_product(pool, client, product, classifier, variant.watermark).await?;
+
+    // Insert immediately if we have transactions
+    let latest_event_time = transactions.iter().map(|t| t.event_time).max();
+    let counts = if transactions.is_empty() {
+        UpsertCounts::default()
+    } else {
+        upsert_transactions_batch(pool, &transactions, insert_batch_size)
+            .await
+            .map_err(|e| e.context("Failed to upsert transactions"))?
+    };
+
+    // Only advance the watermark once the events are stored, and schedule the
+    // next poll from activity including them
+    record_variant_poll(pool, scheduler, product, latest_event_time)
+        .await
+        .map_err(|e| e.context("Failed to record variant sync state"))?;
+
+    Ok(counts)
+}
+
+/// Syncs transactions for the variants the scheduler says are due
+///
+/// Up to `concurrency` variants are fetched at once, sharing the client's rate
+/// limiter, and each is written as soon as its activity arrives. Only events
+/// newer than each variant's watermark are written. Products that fail are
+/// recorded against the sync run and skipped.
+pub async fn sync_transactions(
+    pool: &PgPool,
+    client: &PureApiClient,
+    classifier: &TradeClassifier,
+    scheduler: &PollScheduler,
+    insert_batch_size: usize,
+    concurrency: usize,
+) -> Result<()> {
+    info!("Starting transaction sync");
+    let mut run = SyncRun::start(pool, SyncRunKind::Transactions).await?;
+
+    let variants = match scheduler.due_variants(pool, Utc::now()).await {
+        Ok(variants) => variants,
+        Err(e) => {
+            run.fail(pool, &e).await?;
+            return Err(e);
+        }
+    };
+    info!("Fetching transactions for {} due variants, {} at a time", variants.len(), concurrency);
+
+    let total_products = variants.len();
+
+    // Set once the circuit breaker opens, so variants that haven't started yet
+    // send nothing and stay due for the next sync; those already fetching finish
+    let abort = AtomicBool::new(false);
+    let abort = &abort;
+
+    let mut results = stream::iter(&variants)
+        .map(|variant| async move {
+            if abort.load(Ordering::Relaxed) {
+                return (&variant.product, None);
+            }
+            let result = sync_variant(pool, client, classifier, scheduler, variant, insert_batch_size).await;
+            if matches!(&result, Err(e) if is_circuit_open(e)) {
+                abort.store(true, Ordering::Relaxed);
+            }
+            (&variant.product, Some(result))
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut completed = 0;
+    let mut succeeded = 0;
+    let mut aborted = None;
+    while let Some((product, result)) = results.next().await {
+        let Some(result) = result else { continue };
+        completed += 1;
+        match result {
+            // The API is down; this variant stays due for the next sync too
+            Err(e) if is_circuit_open(&e) => {
+                aborted.get_or_insert(e);
+            }
+            Ok(counts) => {
+                info!(
+                    "[{}/{}] Synced product: {}, variant: {} - {} transactions written",
+                    completed, total_products, product.pure_product_id, product.pure_variant_id,
+                    counts.written()
+                );
+                run.record_attempts(1);
+                run.record_rows(counts);
+                succeeded += 1;
+            }
+            Err(e) => {
+                error!(
+                    "[{}/{}] Failed product: {}, variant: {} - {:#}",
+                    completed, total_products, product.pure_product_id, product.pure_variant_id, e
+                );
+                run.record_attempts(1);
+                run.record_failure(
+                    pool,
+                    Some(&product.pure_product_id),
+                    Some(&product.pure_variant_id),
+                    e.downcast_ref::<PureApiError>().map(PureApiError::kind),
+                    &format!("{:#}", e),
+                ).await;
+
+                if let Err(e) = scheduler.defer_failed_poll(pool, product, Utc::now()).await {
+                    error!("Failed to defer next poll: {}", e);
+                }
+            }
+        }
+    }
+
+    if let Some(e) = aborted {
+        let e = e.context(format!(
+            "Transaction sync aborted with {} of {} variants synced",
+            succeeded, total_products
+        ));
+        error!("{:#}", e);
+        run.finish_aborted(pool, &e).await?;
+        return Err(e);
+    }
+
+    let total_counts = run.counts();
+    info!(
+        "Completed fetching transactions. Total: {} inserted, {} updated from {} products",
+        total_counts.inserted, total_counts.updated, total_products
+    );
+
+    run.finish(pool).await
+}
+
+/// Advances a variant's watermark and schedules its next poll
+async fn record_variant_poll(
+    pool: &PgPool,
+    scheduler: &PollScheduler,
+    product: &Product,
+    latest_event_time: Option<DateTime<Utc>>,
+) -> Result<()> {
+    let schedule = scheduler.schedule_next_poll(pool, product, Utc::now()).await?;
+    record_variant_sync(pool, product, latest_event_time, &schedule).await
+}
+
+/// Upserts a freshly built catalog, marks variants no longer listed as
+/// delisted and records market snapshots
+async fn store_products(
+    pool: &PgPool,
+    client: &PureApiClient,
+    run: &mut SyncRun,
+    insert_batch_size: usize,
+) -> Result<()> {
+    let catalog = client.build_new_products().await.map_err(|e| {
+        error!("Failed to build products: {}", e);
+        e
+    })?;
+    info!("Built {} products ready for insertion", catalog.products.len());
+
+    run.record_attempts(catalog.products_attempted as u64);
+    for batch in &catalog.failed_batches {
+        for product_id in &batch.product_ids {
+            run.record_failure(pool, Some(product_id), None, Some(batch.error_kind), &batch.error).await;
+        }
+    }
+
+    let counts = upsert_products(pool, &catalog.products, insert_batch_size).await.map_err(|e| {
+        error!("Failed to upsert products: {}", e);
+        e
+    })?;
+    run.record_rows(counts);
+
+    record_listings(pool, &catalog.listed_variants).await.map_err(|e| {
+        error!("Failed to record delisted products: {}", e);
+        e
+    })?;
+
+    insert_market_snapshots(pool, &catalog.products).await.map_err(|e| {
+        error!("Failed to record market snapshots: {}", e);
+        e
+    })
+}
+
+/// Syncs products from the API to the database
+pub async fn sync_products(pool: &PgPool, client: &PureApiClient, insert_batch_size: usize) -> Result<()> {
+    info!("Starting product sync");
+    let mut run = SyncRun::start(pool, SyncRunKind::Products).await?;
+
+    match store_products(pool, client, &mut run, insert_batch_size).await {
+        Ok(()) => {
+            run.finish(pool).await?;
+            info!("Product sync completed successfully");
+            Ok(())
+        }
+        Err(e) => {
+            run.fail(pool, &e).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Whether an error came from the Pure API circuit breaker refusing a request
+fn is_circuit_open(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<PureApiError>(), Some(PureApiError::CircuitOpen(_)))
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use common::{SyncRunKind, SyncRunStatus};
+use sqlx::PgPool;
+use std::ops::AddAssign;
+use tracing::{error, info};
+
+/// Rows written by an upsert, split by what happened to each row
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpsertCounts {
+    pub inserted: u64,
+    pub updated: u64,
+    /// Rows that already existed with identical content and were left alone
+    pub unchanged: u64,
+}
+
+impl UpsertCounts {
+    /// Rows the database actually wrote
+    pub fn written(&self) -> u64 {
+        self.inserted + self.updated
+    }
+}
+
+impl AddAssign for UpsertCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+    }
+}
+
+/// An in-progress row in `sync_runs`, finished with `finish` or `fail`
+///
+/// Failures are written to `sync_run_failures` as they happen so a crashed run
+/// still shows what went wrong; totals are written when the run finishes.
+#[derive(Debug)]
+pub struct SyncRun {
+    pub id: i64,
+    kind: SyncRunKind,
+    counts: UpsertCounts,
+    products_attempted: u64,
+    products_failed: u64,
+    first_error: Option<String>,
+}
+
+impl SyncRun {
+    /// Inserts a `running` row for a sync of the given kind
+    pub async fn start(pool: &PgPool, kind: SyncRunKind) -> Result<Self> {
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO sync_runs (kind, started_at, status)
+            VALUES ($1, NOW(), $2)
+            RETURNING id
+            "#
+        )
+        .bind(kind.as_str())
+        .bind(SyncRunStatus::Running.as_str())
+        .fetch_one(pool)
+        .await?;
+
+        info!("Started {} sync run {}", kind.as_str(), id);
+
+        Ok(Self {
+            id,
+            kind,
+            counts: UpsertCounts::default(),
+            products_attempted: 0,
+            products_failed: 0,
+            first_error: None,
+        })
+    }
+
+    pub fn record_attempts(&mut self, products: u64) {
+        self.products_attempted += products;
+    }
+
+    pub fn record_rows(&mut self, counts: UpsertCounts) {
+        self.counts += counts;
+    }
+
+    pub fn counts(&self) -> UpsertCounts {
+        self.counts
+    }
+
+    /// Records a product or variant that failed and was skipped
+    ///
+    /// `error_kind` is the class of a Pure API error, when that's what failed.
+    /// Never fails the run itself; a failure to write the failure is only logged.
+    pub async fn record_failure(
+        &mut self,
+        pool: &PgPool,
+        pure_product_id: Option<&str>,
+        pure_variant_id: Option<&str>,
+        error_kind: Option<&str>,
+        error: &str,
+    ) {
+        self.products_failed += 1;
+        self.first_error.get_or_insert_with(|| error.to_string());
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO sync_run_failures (sync_run_id, pure_product_id, pure_variant_id, error_kind, error)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        )
+        .bind(self.id)
+        .bind(pure_product_id)
+        .bind(pure_variant_id)
+        .bind(error_kind)
+        .bind(error)
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            error!("Failed to record failure for sync run {}: {}", self.id, e);
+        }
+    }
+
+    /// Status and error summary for a run that was not aborted
+    fn outcome(&self) -> (SyncRunStatus, Option<String>) {
+        if self.products_failed == 0 {
+            return (SyncRunStatus::Succeeded, None);
+        }
+
+        let summary = format!(
+            "{} of {} products failed; first error: {}",
+            self.products_failed,
+            self.products_attempted,
+            self.first_error.as_deref().unwrap_or_default()
+        );
+        (SyncRunStatus::Partial, Some(summary))
+    }
+
+    /// Marks the run finished, as partial if any product failed
+    pub async fn finish(self, pool: &PgPool) -> Result<()> {
+        let (status, summary) = self.outcome();
+        self.complete(pool, status, summary).await
+    }
+
+    /// Marks the run partial because `error` stopped it early, keeping what
+    /// was synced before then
+    pub async fn finish_aborted(self, pool: &PgPool, error: &anyhow::Error) -> Result<()> {
+        let summary = match self.outcome() {
+            (_, Some(failures)) => format!("{:#}; {}", error, failures),
+            (_, None) => format!("{:#}", error),
+        };
+        self.complete(pool, SyncRunStatus::Partial, Some(summary)).await
+    }
+
+    /// Marks the run as aborted by `error`
+    pub async fn fail(self, pool: &PgPool, error: &anyhow::Error) -> Result<()> {
+        let summary = format!("{:#}", error);
+        self.complete(pool, SyncRunStatus::Failed, Some(summary)).await
+    }
+
+    async fn complete(self, pool: &PgPool, status: SyncRunStatus, error_summary: Option<String>) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE sync_runs
+            SET finished_at = NOW(),
+                status = $2,
+                products_attempted = $3,
+                products_failed = $4,
+                rows_inserted = $5,
+                rows_updated = $6,
+                rows_unchanged = $7,
+                error_summary = $8
+            WHERE id = $1
+            "#
+        )
+        .bind(self.id)
+        .bind(status.as_str())
+        .bind(self.products_attempted as i32)
+        .bind(self.products_failed as i32)
+        .bind(self.counts.inserted as i64)
+        .bind(self.counts.updated as i64)
+        .bind(self.counts.unchanged as i64)
+        .bind(&error_summary)
+        .execute(pool)
+        .await?;
+
+        info!(
+            "Finished {} sync run {} with status {} ({} rows written, {} of {} products failed)",
+            self.kind.as_str(), self.id, status.as_str(), self.counts.written(), self.products_failed, self.products_attempted
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(products_attempted: u64, products_failed: u64, first_error: Option<&str>) -> SyncRun {
+        SyncRun {
+            id: 1,
+            kind: SyncRunKind::Transactions,
+            counts: UpsertCounts::default(),
+            products_attempted,
+            products_failed,
+            first_error: first_error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_outcome_without_failures_succeeds() {
+        assert_eq!(run(10, 0, None).outcome(), (SyncRunStatus::Succeeded, None));
+    }
+
+    #[test]
+    fn test_outcome_with_failures_is_partial() {
+        let (status, summary) = run(10, 2, Some("timed out")).outcome();
+        assert_eq!(status, SyncRunStatus::Partial);
+        assert_eq!(summary.as_deref(), Some("2 of 10 products failed; first error: timed out"));
+    }
+}
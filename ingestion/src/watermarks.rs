@@ -0,0 +1,50 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use common::Product;
+use sqlx::PgPool;
+
+use crate::scheduler::PollSchedule;
+
+/// Records a successful sync of `product`, advancing its watermark to
+/// `latest_event_time` if that is newer and scheduling its next poll
+pub async fn record_variant_sync(
+    pool: &PgPool,
+    product: &Product,
+    latest_event_time: Option<DateTime<Utc>>,
+    schedule: &PollSchedule,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO variant_sync_state (
+            pure_product_id,
+            pure_variant_id,
+            product_id,
+            watermark,
+            market_data_seen_at,
+            last_synced_at,
+            next_poll_at,
+            poll_interval_secs
+        )
+        VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7)
+        ON CONFLICT (pure_product_id, pure_variant_id)
+        DO UPDATE SET
+            product_id = EXCLUDED.product_id,
+            watermark = GREATEST(variant_sync_state.watermark, EXCLUDED.watermark),
+            market_data_seen_at = EXCLUDED.market_data_seen_at,
+            last_synced_at = NOW(),
+            next_poll_at = EXCLUDED.next_poll_at,
+            poll_interval_secs = EXCLUDED.poll_interval_secs
+        "#
+    )
+    .bind(&product.pure_product_id)
+    .bind(&product.pure_variant_id)
+    .bind(product.id)
+    .bind(latest_event_time)
+    .bind(product.market_data_updated_at)
+    .bind(schedule.next_poll_at)
+    .bind(schedule.interval.num_seconds() as i32)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
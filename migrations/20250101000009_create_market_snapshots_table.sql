@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS market_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    product_id BIGINT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    pure_product_id VARCHAR(255) NOT NULL,
+    pure_variant_id VARCHAR(255) NOT NULL,
+    highest_offer_spot_premium DOUBLE PRECISION,
+    lowest_listing_spot_premium DOUBLE PRECISION,
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_market_snapshots_product_id ON market_snapshots(product_id);
+CREATE INDEX IF NOT EXISTS idx_market_snapshots_pure_ids_captured_at ON market_snapshots(pure_product_id, pure_variant_id, captured_at DESC);
@@ -0,0 +1 @@
+ALTER TABLE transactions ADD COLUMN event_type_reason VARCHAR(100);
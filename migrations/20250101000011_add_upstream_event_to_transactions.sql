@@ -0,0 +1,2 @@
+ALTER TABLE transactions ADD COLUMN upstream_event VARCHAR(50);
+ALTER TABLE transactions ADD COLUMN event_type_source VARCHAR(20);
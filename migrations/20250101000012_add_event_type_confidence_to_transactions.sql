@@ -0,0 +1,4 @@
+ALTER TABLE transactions ADD COLUMN event_type_confidence DOUBLE PRECISION;
+
+-- Supports looking up the previous trade of a variant for tick-based classifiers
+CREATE INDEX IF NOT EXISTS idx_transactions_pure_ids_event_time ON transactions(pure_product_id, pure_variant_id, event_time DESC);
@@ -0,0 +1,19 @@
+CREATE TYPE event_type AS ENUM ('buy', 'sell', 'unknown');
+
+UPDATE transactions
+SET event_type = 'unknown'
+WHERE event_type IS NOT NULL AND LOWER(event_type) NOT IN ('buy', 'sell', 'unknown');
+
+ALTER TABLE transactions
+  ALTER COLUMN event_type TYPE event_type USING LOWER(event_type)::event_type;
+
+CREATE TYPE material AS ENUM ('gold', 'silver', 'platinum', 'palladium', 'copper', 'other');
+
+UPDATE products
+SET material = CASE
+    WHEN LOWER(TRIM(material)) IN ('gold', 'silver', 'platinum', 'palladium', 'copper') THEN LOWER(TRIM(material))
+    ELSE 'other'
+END;
+
+ALTER TABLE products
+  ALTER COLUMN material TYPE material USING material::material;
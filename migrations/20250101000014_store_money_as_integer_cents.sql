@@ -0,0 +1,4 @@
+-- Amounts were already stored as cents in DECIMAL(12, 2) columns; make that explicit
+ALTER TABLE transactions
+  ALTER COLUMN price TYPE BIGINT USING ROUND(price)::BIGINT,
+  ALTER COLUMN spot_premium_dollar TYPE BIGINT USING ROUND(spot_premium_dollar)::BIGINT;
@@ -0,0 +1,18 @@
+ALTER TABLE sync_runs ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'running';
+ALTER TABLE sync_runs ADD COLUMN products_attempted INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sync_runs ADD COLUMN products_failed INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sync_runs ADD COLUMN error_summary TEXT;
+
+-- Runs recorded before statuses existed all completed
+UPDATE sync_runs SET status = 'succeeded' WHERE finished_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS sync_run_failures (
+    id BIGSERIAL PRIMARY KEY,
+    sync_run_id BIGINT NOT NULL REFERENCES sync_runs(id) ON DELETE CASCADE,
+    pure_product_id VARCHAR(255),
+    pure_variant_id VARCHAR(255),
+    error TEXT NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_run_failures_sync_run_id ON sync_run_failures(sync_run_id);
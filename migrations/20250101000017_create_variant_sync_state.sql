@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS variant_sync_state (
+    pure_product_id VARCHAR(255) NOT NULL,
+    pure_variant_id VARCHAR(255) NOT NULL,
+    product_id BIGINT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    watermark TIMESTAMPTZ,
+    market_data_seen_at TIMESTAMPTZ,
+    last_synced_at TIMESTAMPTZ,
+    last_sync_advanced BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (pure_product_id, pure_variant_id)
+);
+
+-- Start from what is already stored so the first incremental sync skips known events
+INSERT INTO variant_sync_state (pure_product_id, pure_variant_id, product_id, watermark)
+SELECT t.pure_product_id, t.pure_variant_id, MIN(t.product_id), MAX(t.event_time)
+FROM transactions t
+GROUP BY t.pure_product_id, t.pure_variant_id
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,7 @@
+ALTER TABLE variant_sync_state ADD COLUMN next_poll_at TIMESTAMPTZ;
+ALTER TABLE variant_sync_state ADD COLUMN poll_interval_secs INTEGER;
+
+-- Superseded by the poll schedule
+ALTER TABLE variant_sync_state DROP COLUMN last_sync_advanced;
+
+CREATE INDEX IF NOT EXISTS idx_variant_sync_state_next_poll_at ON variant_sync_state(next_poll_at);
@@ -0,0 +1 @@
+ALTER TABLE sync_run_failures ADD COLUMN error_kind VARCHAR(20);
@@ -0,0 +1,30 @@
+-- One row per version of a product's catalog fields; the current version has no valid_to
+CREATE TABLE IF NOT EXISTS product_history (
+    id BIGSERIAL PRIMARY KEY,
+    product_id BIGINT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    pure_product_id VARCHAR(255) NOT NULL,
+    pure_variant_id VARCHAR(255) NOT NULL,
+    name VARCHAR(500) NOT NULL,
+    sku VARCHAR(255) NOT NULL,
+    material material NOT NULL,
+    variant_label VARCHAR(255) NOT NULL,
+    image_url VARCHAR(500),
+    -- Fields that differ from the previous version; empty for the first
+    changed_fields TEXT[] NOT NULL DEFAULT '{}',
+    valid_from TIMESTAMPTZ NOT NULL,
+    valid_to TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_product_history_current
+    ON product_history(product_id) WHERE valid_to IS NULL;
+CREATE INDEX IF NOT EXISTS idx_product_history_pure_ids
+    ON product_history(pure_product_id, pure_variant_id, valid_from);
+
+-- What is stored now is the first version we know of
+INSERT INTO product_history (
+    product_id, pure_product_id, pure_variant_id,
+    name, sku, material, variant_label, image_url,
+    valid_from
+)
+SELECT id, pure_product_id, pure_variant_id, name, sku, material, variant_label, image_url, created_at
+FROM products;
@@ -0,0 +1,2 @@
+-- Set when a variant disappears from Pure's product options, cleared if it comes back
+ALTER TABLE products ADD COLUMN delisted_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+-- Keyset pagination of /transactions walks (event_time, id) newest first
+CREATE INDEX IF NOT EXISTS idx_transactions_event_time_id ON transactions(event_time DESC, id DESC);
+DROP INDEX IF EXISTS idx_transactions_event_time;
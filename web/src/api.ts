@@ -1,12 +1,29 @@
-import type { TransactionsResponse, ProductDetailsResponse, ProductStatsResponse } from './types';
+import type { AnalyticsOptions, AnalyticsResponse, ApiErrorResponse, ProductStatsOptions, CandlesOptions, CandlesResponse, TransactionFilters, TransactionsResponse, ProductDetailsResponse, ProductHistoryResponse, ProductStatsResponse, SyncStatusResponse } from './types';
 
 const API_BASE_URL = import.meta.env.VITE_API_URL || 'http://localhost:3000';
 
-export async function fetchTransactions(): Promise<TransactionsResponse> {
-  const response = await fetch(`${API_BASE_URL}/transactions`);
+// Describes a failed response using the API's JSON error body, when it has one
+async function errorMessage(response: Response, action: string): Promise<string> {
+  try {
+    const body: ApiErrorResponse = await response.json();
+    return `${action}: ${body.error.message} (request ${body.error.request_id})`;
+  } catch {
+    return `${action}: ${response.statusText}`;
+  }
+}
+
+export async function fetchTransactions(filters: TransactionFilters = {}): Promise<TransactionsResponse> {
+  const params = new URLSearchParams();
+  for (const [key, value] of Object.entries(filters)) {
+    if (value !== undefined && value !== '') {
+      params.set(key, String(value));
+    }
+  }
+
+  const response = await fetch(`${API_BASE_URL}/transactions?${params}`);
 
   if (!response.ok) {
-    throw new Error(`Failed to fetch transactions: ${response.statusText}`);
+    throw new Error(await errorMessage(response, 'Failed to fetch transactions'));
   }
 
   return response.json();
@@ -16,17 +33,78 @@ export async function fetchProductDetails(productId: string): Promise<ProductDet
   const response = await fetch(`${API_BASE_URL}/product/${encodeURIComponent(productId)}`);
 
   if (!response.ok) {
-    throw new Error(`Failed to fetch product details: ${response.statusText}`);
+    throw new Error(await errorMessage(response, 'Failed to fetch product details'));
+  }
+
+  return response.json();
+}
+
+export async function fetchProductHistory(productId: string): Promise<ProductHistoryResponse> {
+  const response = await fetch(`${API_BASE_URL}/product/${encodeURIComponent(productId)}/history`);
+
+  if (!response.ok) {
+    throw new Error(await errorMessage(response, 'Failed to fetch product history'));
+  }
+
+  return response.json();
+}
+
+export async function fetchProductCandles(productId: string, options: CandlesOptions = {}): Promise<CandlesResponse> {
+  const params = new URLSearchParams();
+  for (const [key, value] of Object.entries(options)) {
+    if (value !== undefined && value !== '') {
+      params.set(key, String(value));
+    }
+  }
+
+  const response = await fetch(`${API_BASE_URL}/product/${encodeURIComponent(productId)}/candles?${params}`);
+
+  if (!response.ok) {
+    throw new Error(await errorMessage(response, 'Failed to fetch product candles'));
+  }
+
+  return response.json();
+}
+
+export async function fetchProductAnalytics(productId: string, options: AnalyticsOptions = {}): Promise<AnalyticsResponse> {
+  const params = new URLSearchParams();
+  for (const [key, value] of Object.entries(options)) {
+    if (value !== undefined && value !== '') {
+      params.set(key, String(value));
+    }
+  }
+
+  const response = await fetch(`${API_BASE_URL}/product/${encodeURIComponent(productId)}/analytics?${params}`);
+
+  if (!response.ok) {
+    throw new Error(await errorMessage(response, 'Failed to fetch product analytics'));
+  }
+
+  return response.json();
+}
+
+export async function fetchProductStats(options: ProductStatsOptions = {}): Promise<ProductStatsResponse> {
+  const params = new URLSearchParams();
+  for (const [key, value] of Object.entries(options)) {
+    if (value !== undefined && value !== '') {
+      params.set(key, String(value));
+    }
+  }
+
+  const response = await fetch(`${API_BASE_URL}/products/stats?${params}`);
+
+  if (!response.ok) {
+    throw new Error(await errorMessage(response, 'Failed to fetch product stats'));
   }
 
   return response.json();
 }
 
-export async function fetchProductStats(): Promise<ProductStatsResponse> {
-  const response = await fetch(`${API_BASE_URL}/products/stats`);
+export async function fetchSyncStatus(): Promise<SyncStatusResponse> {
+  const response = await fetch(`${API_BASE_URL}/sync/status`);
 
   if (!response.ok) {
-    throw new Error(`Failed to fetch product stats: ${response.statusText}`);
+    throw new Error(await errorMessage(response, 'Failed to fetch sync status'));
   }
 
   return response.json();
@@ -1,5 +1,5 @@
 import { useEffect, useState } from "react";
-import type { ProductStats } from "../types";
+import type { ProductStats, StatsWindow } from "../types";
 import { fetchProductStats } from "../api";
 
 function getMaterialBadgeClass(material: string): string {
@@ -22,6 +22,29 @@ function getMaterialBadgeClass(material: string): string {
   return "bg-gray-500/20 text-gray-300 border border-gray-500/30";
 }
 
+const WINDOWS: { value: StatsWindow; label: string }[] = [
+  { value: "24h", label: "24 hours" },
+  { value: "7d", label: "7 days" },
+  { value: "30d", label: "30 days" },
+  { value: "90d", label: "90 days" },
+  { value: "all", label: "All time" },
+];
+
+// Percent change against the previous window, colored by direction
+function TrendIndicator({ change }: { change: number | null | undefined }) {
+  if (change === null || change === undefined) {
+    return null;
+  }
+  if (change === 0) {
+    return <span className="ml-2 text-xs text-slate-400">0%</span>;
+  }
+  return (
+    <span className={`ml-2 text-xs ${change > 0 ? "text-green-400" : "text-red-400"}`}>
+      {change > 0 ? "▲" : "▼"} {Math.abs(change).toFixed(0)}%
+    </span>
+  );
+}
+
 type SortColumn =
   | "material"
   | "name"
@@ -48,12 +71,13 @@ export function ProductStatsComponent({ onProductClick }: ProductStatsProps) {
   const [sortDirection, setSortDirection] = useState<SortDirection>("desc");
   const [searchQuery, setSearchQuery] = useState<string>("");
   const [selectedMaterials, setSelectedMaterials] = useState<Set<string>>(new Set());
+  const [statsWindow, setStatsWindow] = useState<StatsWindow>("all");
 
   useEffect(() => {
     async function loadProductStats() {
       try {
         setLoading(true);
-        const data = await fetchProductStats();
+        const data = await fetchProductStats({ window: statsWindow, compare: statsWindow !== "all" });
         setProducts(data.products);
         setError(null);
       } catch (err) {
@@ -66,7 +90,7 @@ export function ProductStatsComponent({ onProductClick }: ProductStatsProps) {
     }
 
     loadProductStats();
-  }, []);
+  }, [statsWindow]);
 
   const handleSort = (column: SortColumn) => {
     if (sortColumn === column) {
@@ -226,6 +250,22 @@ export function ProductStatsComponent({ onProductClick }: ProductStatsProps) {
           </div>
         </div>
 
+        <div className="flex gap-2 mb-6">
+          {WINDOWS.map(({ value, label }) => (
+            <button
+              key={value}
+              onClick={() => setStatsWindow(value)}
+              className={`px-3 py-1 text-sm rounded-full border transition-colors ${
+                statsWindow === value
+                  ? "bg-emerald-500/20 text-emerald-400 border-emerald-500/30"
+                  : "text-slate-400 border-slate-600 hover:text-white hover:border-slate-500"
+              }`}
+            >
+              {label}
+            </button>
+          ))}
+        </div>
+
         <div className="mb-6">
           <input
             type="text"
@@ -380,6 +420,7 @@ export function ProductStatsComponent({ onProductClick }: ProductStatsProps) {
                   </td>
                   <td className="px-6 py-4 text-sm text-gray-300">
                     {product.transaction_count}
+                    <TrendIndicator change={product.percent_change?.transaction_count} />
                   </td>
                   <td className="px-6 py-4 text-sm font-medium text-white">
                     $
@@ -388,6 +429,7 @@ export function ProductStatsComponent({ onProductClick }: ProductStatsProps) {
                           .toFixed(2)
                           .replace(/\B(?=(\d{3})+(?!\d))/g, ",")
                       : "0.00"}
+                    <TrendIndicator change={product.percent_change?.total_volume} />
                   </td>
                   <td className="px-6 py-4 text-sm text-gray-300">
                     {product.buy_count}
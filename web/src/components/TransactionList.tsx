@@ -36,12 +36,18 @@ export function TransactionList({ onProductClick }: TransactionListProps) {
   const [sortColumn, setSortColumn] = useState<SortColumn>('event_time');
   const [sortDirection, setSortDirection] = useState<SortDirection>('desc');
 
+  const [nextCursor, setNextCursor] = useState<string | null>(null);
+  const [totalCount, setTotalCount] = useState<number | null>(null);
+  const [loadingMore, setLoadingMore] = useState(false);
+
   useEffect(() => {
     async function loadTransactions() {
       try {
         setLoading(true);
-        const data = await fetchTransactions();
+        const data = await fetchTransactions({ include_total: true });
         setTransactions(data.transactions);
+        setNextCursor(data.next_cursor);
+        setTotalCount(data.total_count);
         setError(null);
       } catch (err) {
         setError(err instanceof Error ? err.message : 'Failed to load transactions');
@@ -53,6 +59,20 @@ export function TransactionList({ onProductClick }: TransactionListProps) {
     loadTransactions();
   }, []);
 
+  const loadMore = async () => {
+    if (!nextCursor) return;
+    try {
+      setLoadingMore(true);
+      const data = await fetchTransactions({ cursor: nextCursor });
+      setTransactions(current => [...current, ...data.transactions]);
+      setNextCursor(data.next_cursor);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to load transactions');
+    } finally {
+      setLoadingMore(false);
+    }
+  };
+
   const handleSort = (column: SortColumn) => {
     if (sortColumn === column) {
       setSortDirection(sortDirection === 'asc' ? 'desc' : 'asc');
@@ -160,7 +180,7 @@ export function TransactionList({ onProductClick }: TransactionListProps) {
         <div className="flex gap-4 mb-6">
           <div className="bg-slate-900 rounded-lg p-4">
             <span className="text-lg font-medium text-white">
-              Total Transactions: {transactions.length}
+              Total Transactions: {totalCount ?? transactions.length}
             </span>
           </div>
           <div className="bg-slate-900 rounded-lg p-4">
@@ -244,8 +264,8 @@ export function TransactionList({ onProductClick }: TransactionListProps) {
               </tr>
             </thead>
             <tbody className="divide-y divide-slate-700">
-              {sortedTransactions.map((tx, index) => (
-                <tr key={index} className="hover:bg-slate-700 transition-colors">
+              {sortedTransactions.map((tx) => (
+                <tr key={tx.id} className="hover:bg-slate-700 transition-colors">
                   <td className="px-2 py-4">
                     {tx.image_url ? (
                       <img
@@ -314,6 +334,18 @@ export function TransactionList({ onProductClick }: TransactionListProps) {
             </tbody>
           </table>
         </div>
+
+        {nextCursor && (
+          <div className="flex justify-center mt-6">
+            <button
+              onClick={loadMore}
+              disabled={loadingMore}
+              className="px-4 py-2 rounded-lg bg-slate-800 text-white hover:bg-slate-700 transition-colors disabled:opacity-50"
+            >
+              {loadingMore ? 'Loading...' : `Load more (${transactions.length} of ${totalCount ?? '?'})`}
+            </button>
+          </div>
+        )}
       </div>
     </div>
   );
@@ -1,5 +1,7 @@
 export interface Transaction {
+  id: number;
   pure_product_id: string;
+  pure_variant_id: string;
   name: string;
   sku: string;
   material: string;
@@ -11,10 +13,33 @@ export interface Transaction {
   spot_premium_percentage: number;
   spot_premium_dollar: number;
   event_type: string | null;
+  event_type_confidence: number | null;
+  event_type_source: string | null;
+  upstream_event: string | null;
 }
 
 export interface TransactionsResponse {
   transactions: Transaction[];
+  next_cursor: string | null;
+  total_count: number | null;
+}
+
+export interface TransactionFilters {
+  limit?: number;
+  cursor?: string;
+  material?: string;
+  product_id?: string;
+  variant_id?: string;
+  event_type?: string;
+  from?: string;
+  to?: string;
+  min_price?: number;
+  max_price?: number;
+  min_premium?: number;
+  max_premium?: number;
+  min_quantity?: number;
+  max_quantity?: number;
+  include_total?: boolean;
 }
 
 export interface Product {
@@ -23,6 +48,7 @@ export interface Product {
   material: string;
   variant_label: string;
   image_url: string | null;
+  delisted_at: string | null;
 }
 
 export interface ProductTransaction {
@@ -34,6 +60,9 @@ export interface ProductTransaction {
   spot_premium_percentage: number;
   spot_premium_dollar: number;
   event_type: string | null;
+  event_type_confidence: number | null;
+  event_type_source: string | null;
+  upstream_event: string | null;
 }
 
 export interface ProductDetailsResponse {
@@ -41,6 +70,42 @@ export interface ProductDetailsResponse {
   transactions: ProductTransaction[];
 }
 
+export interface FieldChange {
+  field: string;
+  old: string | null;
+  new: string | null;
+}
+
+export interface ProductRevision {
+  pure_variant_id: string;
+  valid_from: string;
+  valid_to: string | null;
+  name: string;
+  sku: string;
+  material: string;
+  variant_label: string;
+  image_url: string | null;
+  changes: FieldChange[];
+}
+
+export interface ProductHistoryResponse {
+  revisions: ProductRevision[];
+}
+
+export interface StatsMetrics {
+  transaction_count: number;
+  buy_count: number;
+  sell_count: number;
+  buy_sell_ratio: number | null;
+  total_volume: number | null;
+  total_buy_quantity: number | null;
+  total_sell_quantity: number | null;
+  total_buy_amount: number | null;
+  total_sell_amount: number | null;
+}
+
+export type StatsChanges = { [K in keyof StatsMetrics]: number | null };
+
 export interface ProductStats {
   pure_product_id: string;
   material: string;
@@ -56,8 +121,111 @@ export interface ProductStats {
   total_sell_quantity: number | null;
   total_buy_amount: number | null;
   total_sell_amount: number | null;
+  delisted_at: string | null;
+  delisted_variant_count: number;
+  previous: StatsMetrics | null;
+  percent_change: StatsChanges | null;
 }
 
 export interface ProductStatsResponse {
+  from: string | null;
+  to: string | null;
+  previous_from: string | null;
+  previous_to: string | null;
   products: ProductStats[];
 }
+
+export type StatsWindow = '24h' | '7d' | '30d' | '90d' | 'all';
+
+export interface ProductStatsOptions {
+  material?: string;
+  min_confidence?: number;
+  status?: 'active' | 'delisted';
+  window?: StatsWindow;
+  from?: string;
+  to?: string;
+  compare?: boolean;
+}
+
+export interface SyncKindStatus {
+  kind: string;
+  last_run_id: number;
+  last_status: string;
+  last_started_at: string;
+  last_finished_at: string | null;
+  last_completed_at: string | null;
+}
+
+export interface SyncStatusResponse {
+  data_as_of: string | null;
+  kinds: SyncKindStatus[];
+}
+
+export type CandleInterval = '1h' | '1d' | '1w' | '1M';
+
+export interface Candle {
+  bucket_start: string;
+  open_price: number;
+  high_price: number;
+  low_price: number;
+  close_price: number;
+  open_premium: number;
+  high_premium: number;
+  low_premium: number;
+  close_premium: number;
+  volume: number;
+  quantity: number;
+  trade_count: number;
+}
+
+export interface CandlesResponse {
+  tz: string;
+  candles: Candle[];
+}
+
+export interface CandlesOptions {
+  interval?: CandleInterval;
+  variant_id?: string;
+  from?: string;
+  to?: string;
+  tz?: string;
+}
+
+export interface PriceAnalytics {
+  trade_count: number;
+  quantity: number;
+  volume: number | null;
+  vwap: number | null;
+  vw_premium_percentage: number | null;
+  vw_premium_dollar: number | null;
+  median_premium_percentage: number | null;
+  p10_premium_percentage: number | null;
+  p90_premium_percentage: number | null;
+}
+
+export interface VariantAnalytics {
+  pure_variant_id: string;
+  variant_label: string;
+  all: PriceAnalytics;
+  buy: PriceAnalytics | null;
+  sell: PriceAnalytics | null;
+}
+
+export interface AnalyticsResponse {
+  variants: VariantAnalytics[];
+}
+
+export interface AnalyticsOptions {
+  variant_id?: string;
+  from?: string;
+  to?: string;
+  min_confidence?: number;
+}
+
+export interface ApiErrorResponse {
+  error: {
+    code: string;
+    message: string;
+    request_id: string | null;
+  };
+}